@@ -0,0 +1,120 @@
+use color_eyre::eyre::{bail, Report, Result, WrapErr as _};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{num::NonZeroUsize, str::FromStr};
+
+/// Distribution of intervals between consecutive transactions.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", deny_unknown_fields)]
+pub enum Arrival {
+    /// Equal intervals.
+    #[default]
+    Fixed,
+    /// Exponentially distributed intervals, so transactions arrive as a Poisson process.
+    Poisson,
+    /// Transactions arrive in bursts of `burst_size` at once with equal intervals between bursts.
+    Bursty { burst_size: NonZeroUsize },
+}
+
+impl Arrival {
+    /// Returns the interval in seconds to the next transaction for the given average rate.
+    /// `burst_left` is the amount of transactions left in the current burst.
+    pub fn next_gap(&self, tps: f64, rng: &mut impl Rng, burst_left: &mut usize) -> f64 {
+        match *self {
+            Arrival::Fixed => 1.0 / tps,
+            Arrival::Poisson => {
+                let uniform: f64 = rng.gen();
+                -(1.0 - uniform).ln() / tps
+            }
+            Arrival::Bursty { burst_size } => {
+                if *burst_left > 0 {
+                    *burst_left -= 1;
+                    0.0
+                } else {
+                    *burst_left = burst_size.get() - 1;
+                    burst_size.get() as f64 / tps
+                }
+            }
+        }
+    }
+}
+
+/// Parses the arrival process given on the command line as `fixed`, `poisson` or `bursty:SIZE`.
+impl FromStr for Arrival {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let arrival = match s.split_once(':') {
+            None if s == "fixed" => Arrival::Fixed,
+            None if s == "poisson" => Arrival::Poisson,
+            Some(("bursty", size)) => Arrival::Bursty {
+                burst_size: size
+                    .parse()
+                    .wrap_err_with(|| format!("Invalid burst size in `{}`", s))?,
+            },
+            _ => bail!("Unknown arrival process `{}`", s),
+        };
+        Ok(arrival)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn fixed_gaps_are_equal() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut burst_left = 0;
+        for _ in 0..10 {
+            assert_eq!(
+                Arrival::Fixed.next_gap(4.0, &mut rng, &mut burst_left),
+                0.25
+            );
+        }
+    }
+
+    #[test]
+    fn poisson_gaps_average_to_the_rate() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut burst_left = 0;
+        let samples = 100_000;
+        let total: f64 = (0..samples)
+            .map(|_| Arrival::Poisson.next_gap(10.0, &mut rng, &mut burst_left))
+            .inspect(|gap| assert!(*gap >= 0.0))
+            .sum();
+        let mean = total / f64::from(samples);
+        assert!((mean - 0.1).abs() < 0.005, "{}", mean);
+    }
+
+    #[test]
+    fn bursts_keep_the_average_rate() {
+        let arrival: Arrival = "bursty:4".parse().expect("Valid arrival");
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut burst_left = 0;
+        let gaps = (0..8)
+            .map(|_| arrival.next_gap(2.0, &mut rng, &mut burst_left))
+            .collect::<Vec<_>>();
+        assert_eq!(gaps, [2.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn same_seed_gives_same_gaps() {
+        let gaps = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut burst_left = 0;
+            (0..5)
+                .map(|_| Arrival::Poisson.next_gap(1.0, &mut rng, &mut burst_left))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(gaps(7), gaps(7));
+    }
+
+    #[test]
+    fn rejects_invalid_arrivals() {
+        for s in ["uniform", "bursty", "bursty:0", "bursty:many", "fixed:1"] {
+            assert!(s.parse::<Arrival>().is_err(), "{}", s);
+        }
+    }
+}
@@ -1,26 +1,86 @@
-use std::fmt::Debug;
-
-use super::http::AsyncRequestBuilder;
-use color_eyre::eyre::{eyre, Context, Result};
-use futures_util::stream::StreamExt;
-use hyper::{client::HttpConnector, Client as HyperClient};
-use iroha_client::client::{Client as IrohaClient, QueryResponseHandler};
-use iroha_crypto::HashOf;
-use iroha_data_model::{
-    events::pipeline::PipelineRejectionReason, prelude::*, transaction::TransactionPayload,
+use std::{fmt::Debug, str::FromStr, sync::Arc, time::Duration};
+
+use super::{
+    events::EventMultiplexer,
+    http::AsyncRequestBuilder,
+    peers::{Peer, PeerHealth, Peers, SubmitStrategy},
 };
-use tokio::{
-    spawn,
-    sync::{mpsc, oneshot},
+use crate::{
+    config::PeerEndpoint,
+    metrics::{Encoder, MetricKind},
+    run_id::{RunId, METADATA_OPERATION, METADATA_RUN_ID, METADATA_SENT_AT_MS},
 };
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{bail, eyre, Context, Result};
+use hyper::{client::HttpConnector, Client as HyperClient, StatusCode, Uri};
+use iroha_client::client::{
+    events_api::AsyncEventStream, Client as IrohaClient, QueryResponseHandler,
+};
+use iroha_config::client::Configuration;
+use iroha_crypto::{Hash, HashOf};
+use iroha_data_model::{
+    events::pipeline::PipelineRejectionReason,
+    prelude::*,
+    transaction::{error::TransactionRejectionReason, TransactionPayload},
+};
+use tokio::{task, time};
+use tracing::debug;
+
+/// Time to establish a connection to a peer.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time to wait for a peer to answer a submission or a health probe, so a peer which
+/// accepts connections but never answers is taken out of rotation as well.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone)]
 pub struct Client {
+    /// Client of the first peer, used for everything except submissions.
     iroha_client: IrohaClient,
+    peers: Arc<Peers>,
     hyper_client: HyperClient<HttpConnector>,
+    events: Arc<EventMultiplexer>,
+    status_timeout: Duration,
 }
 
 impl Client {
+    /// Makes a client submitting transactions to the given peers according to the strategy.
+    ///
+    /// # Errors
+    /// if a client for some peer can't be made or some peer is not reachable over plain HTTP.
+    pub fn new(
+        config: &Configuration,
+        endpoints: &[PeerEndpoint],
+        strategy: SubmitStrategy,
+    ) -> Result<Self> {
+        // Submissions go through `HttpConnector`, which can't speak TLS
+        if let Some(endpoint) = endpoints
+            .iter()
+            .find(|endpoint| endpoint.url.scheme() != "http")
+        {
+            bail!(
+                "Peer `{}` is not an `http` URL, other schemes are not supported",
+                endpoint.url
+            );
+        }
+        let peers = Peers::new(config, endpoints, strategy)?;
+        let mut connector = HttpConnector::new();
+        connector.set_connect_timeout(Some(CONNECT_TIMEOUT));
+        Ok(Self {
+            iroha_client: peers.first().iroha_client.clone(),
+            peers: Arc::new(peers),
+            hyper_client: HyperClient::builder().build(connector),
+            events: Arc::default(),
+            status_timeout: Duration::from_millis(config.transaction_status_timeout_ms),
+        })
+    }
+
+    /// Sets how long blocking submits wait for the outcome of the transaction instead of
+    /// `TRANSACTION_STATUS_TIMEOUT_MS` of the configuration.
+    pub fn with_status_timeout(mut self, status_timeout: Duration) -> Self {
+        self.status_timeout = status_timeout;
+        self
+    }
+
     #[allow(dead_code)]
     pub async fn submit(
         &self,
@@ -52,21 +112,247 @@ impl Client {
         instructions: impl IntoIterator<Item = InstructionExpr>,
         metadata: UnlimitedMetadata,
     ) -> Result<HashOf<TransactionPayload>> {
-        self.submit_transaction(
-            self.iroha_client
-                .build_transaction(instructions, metadata)?,
-        )
-        .await
+        self.submit_transaction(self.build_transaction(instructions, metadata)?)
+            .await
+    }
+
+    pub fn build_transaction(
+        &self,
+        instructions: impl IntoIterator<Item = InstructionExpr>,
+        metadata: UnlimitedMetadata,
+    ) -> Result<SignedTransaction> {
+        self.iroha_client.build_transaction(instructions, metadata)
+    }
+
+    /// Chooses the peer to submit the transaction to.
+    pub fn choose_peer(&self, transaction: &SignedTransaction) -> &Peer {
+        self.peers.choose(transaction)
     }
 
     pub async fn submit_transaction(
         &self,
         transaction: SignedTransaction,
     ) -> Result<HashOf<TransactionPayload>> {
-        self.iroha_client.submit_transaction(&transaction)
+        self.submit_transaction_to(self.choose_peer(&transaction), transaction)
+            .await
+    }
+
+    /// Submits the transaction to the peer through the hyper client, so no worker thread
+    /// is blocked while waiting for Torii.
+    pub async fn submit_transaction_to(
+        &self,
+        peer: &Peer,
+        transaction: SignedTransaction,
+    ) -> Result<HashOf<TransactionPayload>> {
+        let (request, hash) = peer
+            .iroha_client
+            .prepare_transaction_request::<AsyncRequestBuilder>(&transaction);
+        let request = request
+            .build()
+            .wrap_err("Failed to build the transaction request")?;
+        let response = match time::timeout(RESPONSE_TIMEOUT, request.send(&self.hyper_client)).await
+        {
+            Ok(Ok(response)) => response,
+            Ok(Err(err)) => {
+                peer.submit_failed();
+                return Err(err).wrap_err_with(|| {
+                    format!(
+                        "Failed to send the transaction with hash {} to {}",
+                        hash,
+                        peer.name()
+                    )
+                });
+            }
+            Err(_) => {
+                peer.submit_failed();
+                return Err(eyre!(
+                    "No response to the transaction with hash {} from {} in {:?}",
+                    hash,
+                    peer.name(),
+                    RESPONSE_TIMEOUT
+                ));
+            }
+        };
+        if response.status().is_server_error() {
+            peer.submit_failed();
+        } else {
+            peer.is_responding();
+        }
+        if response.status() != StatusCode::OK {
+            return Err(eyre!(
+                "Failed to submit the transaction with hash {} to {}: {} {}",
+                hash,
+                peer.name(),
+                response.status(),
+                String::from_utf8_lossy(response.body())
+            ));
+        }
+        Ok(hash)
+    }
+
+    /// Probes the health endpoint of every peer out of rotation and brings back
+    /// the ones which respond.
+    pub async fn probe_unhealthy_peers(&self) {
+        for peer in self.peers.iter().filter(|peer| !peer.is_healthy()) {
+            match self.probe(peer).await {
+                Ok(()) => peer.is_responding(),
+                Err(err) => debug!(peer = %peer.name(), "Peer is still unhealthy: {}", err),
+            }
+        }
+    }
+
+    async fn probe(&self, peer: &Peer) -> Result<()> {
+        let uri: Uri = peer
+            .health_url()?
+            .as_str()
+            .parse()
+            .wrap_err("Failed to parse the health URL")?;
+        let response = time::timeout(RESPONSE_TIMEOUT, self.hyper_client.get(uri))
+            .await
+            .map_err(|_| {
+                eyre!(
+                    "No response from the health endpoint in {:?}",
+                    RESPONSE_TIMEOUT
+                )
+            })?
+            .wrap_err("Failed to reach the health endpoint")?;
+        if !response.status().is_success() {
+            return Err(eyre!("Health endpoint answered {}", response.status()));
+        }
+        Ok(())
+    }
+
+    /// Health of every peer.
+    pub fn peer_health(&self) -> Vec<PeerHealth> {
+        self.peers.iter().map(Peer::health).collect()
+    }
+
+    pub fn encode_metrics(&self, encoder: &mut Encoder) {
+        encoder.family(
+            "peer_up",
+            MetricKind::Gauge,
+            "Whether the peer is in rotation for submissions.",
+        );
+        for peer in self.peers.iter() {
+            encoder.sample(
+                "peer_up",
+                &[("peer", peer.name())],
+                u8::from(peer.is_healthy()),
+            );
+        }
+    }
+
+    /// Runs queries through the blocking Iroha client on a thread where blocking is allowed.
+    pub async fn query<T, F>(&self, queries: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&IrohaClient) -> Result<T> + Send + 'static,
+    {
+        let iroha_client = self.iroha_client.clone();
+        task::spawn_blocking(move || queries(&iroha_client))
+            .await
+            .wrap_err("Failed to run queries")?
+    }
+
+    /// Finds transactions of the blocks committed at or after `since`, so outcomes missed
+    /// by the event stream can be recovered. Blocks are queried from the newest one.
+    pub async fn find_block_transactions_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<BlockTransaction>> {
+        let (_, found) = self
+            .scan_blocks_since(Some(since), |_, hash, rejection, _| {
+                Some(BlockTransaction {
+                    hash,
+                    rejection: rejection.cloned().map(PipelineRejectionReason::Transaction),
+                })
+            })
+            .await?;
+        Ok(found)
+    }
+
+    /// Finds transactions tagged with the run id in the blocks committed at or after `since`,
+    /// or in all blocks. Returns them together with the amount of scanned blocks.
+    pub async fn find_run_transactions_since(
+        &self,
+        since: Option<DateTime<Utc>>,
+        run_id: &RunId,
+    ) -> Result<(usize, Vec<RunTransaction>)> {
+        let run_id = run_id.to_string();
+        self.scan_blocks_since(since, move |committed_at_ms, hash, rejection, metadata| {
+            let string = |key| match metadata.get(&metadata_key(key)) {
+                Some(Value::String(string)) => Some(string.clone()),
+                _ => None,
+            };
+            let number = |key| match metadata.get(&metadata_key(key)) {
+                Some(Value::Numeric(NumericValue::U64(number))) => Some(*number),
+                _ => None,
+            };
+            if string(METADATA_RUN_ID)? != run_id {
+                return None;
+            }
+            Some(RunTransaction {
+                hash,
+                is_rejected: rejection.is_some(),
+                operation: string(METADATA_OPERATION),
+                sent_at_ms: number(METADATA_SENT_AT_MS),
+                committed_at_ms,
+            })
+        })
+        .await
+    }
+
+    /// Walks the blocks from the newest one down to `since`, extracting values from their
+    /// transactions by the block timestamp, the hash, the rejection reason and the metadata.
+    async fn scan_blocks_since<T, F>(
+        &self,
+        since: Option<DateTime<Utc>>,
+        mut extract: F,
+    ) -> Result<(usize, Vec<T>)>
+    where
+        T: Send + 'static,
+        F: FnMut(u64, Hash, Option<&TransactionRejectionReason>, &UnlimitedMetadata) -> Option<T>
+            + Send
+            + 'static,
+    {
+        let since_ms = since.map_or(0, |since| {
+            u64::try_from(since.timestamp_millis()).unwrap_or_default()
+        });
+        self.query(move |iroha_client| {
+            let mut scanned = 0;
+            let mut found = Vec::new();
+            let blocks = iroha_client
+                .request(FindAllBlocks)
+                .wrap_err("Failed to query blocks")?;
+            for block in blocks {
+                let block = block.wrap_err("Failed to fetch a block")?;
+                let payload = block.payload();
+                let timestamp_ms = payload.header.timestamp_ms;
+                if timestamp_ms < since_ms {
+                    break;
+                }
+                scanned += 1;
+                found.extend(payload.transactions.iter().filter_map(|tx| {
+                    extract(
+                        timestamp_ms,
+                        tx.value.hash().into(),
+                        tx.error.as_ref(),
+                        &tx.value.payload().metadata,
+                    )
+                }));
+            }
+            Ok((scanned, found))
+        })
+        .await
+        .wrap_err("Failed to scan blocks")
+    }
+
+    pub async fn listen_for_events(&self, event_filter: FilterBox) -> Result<AsyncEventStream> {
+        self.iroha_client
+            .listen_for_events_async(event_filter)
+            .await
     }
 
-    #[allow(dead_code)]
     pub async fn submit_blocking(
         &self,
         instruction: impl Into<InstructionExpr>,
@@ -87,9 +373,7 @@ impl Client {
         instructions: impl IntoIterator<Item = InstructionExpr>,
         metadata: UnlimitedMetadata,
     ) -> Result<SubmitBlockingStatus> {
-        let transaction = self
-            .iroha_client
-            .build_transaction(instructions, metadata)?;
+        let transaction = self.build_transaction(instructions, metadata)?;
         self.submit_transaction_blocking(transaction).await
     }
 
@@ -97,74 +381,74 @@ impl Client {
         &self,
         transaction: SignedTransaction,
     ) -> Result<SubmitBlockingStatus> {
-        let iroha_client = self.iroha_client.clone();
-        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
-        let (init_sender, init_receiver) = oneshot::channel::<()>();
-        let hash = transaction.hash();
-        spawn(async move {
-            let mut event_stream = iroha_client
-                .listen_for_events_async(PipelineEventFilter::new().hash(hash.into()).into())
-                .await
-                .expect("Failed to establish event listener connection.");
-            init_sender
-                .send(())
-                .expect("Failed to send init message through init channel.");
-            while let Some(event) = event_stream.next().await {
-                let event = event.expect("Failed to listen for the event stream.");
-                if let Event::Pipeline(this_event) = event {
-                    match this_event.status() {
-                        PipelineStatus::Validating => {}
-                        PipelineStatus::Rejected(reason) => {
-                            return event_sender
-                                .send(SubmitBlockingStatus::Rejected(reason.clone()))
-                                .expect("Failed to send the transaction through event channel.")
-                        }
-                        PipelineStatus::Committed => {
-                            return event_sender
-                                .send(SubmitBlockingStatus::Committed(hash))
-                                .expect("Failed to send the transaction through event channel.")
-                        }
-                    }
-                } else {
-                    return event_sender
-                        .send(SubmitBlockingStatus::Unknown)
-                        .expect("Failed to send the transaction through event channel.");
-                }
-            }
-        });
-        init_receiver
+        self.submit_transaction_blocking_to(self.choose_peer(&transaction), transaction)
             .await
-            .wrap_err("Failed to receive init message.")?;
-        self.submit_transaction(transaction).await?;
-        let res = event_receiver
-            .recv()
+    }
+
+    /// Submits the transaction to the peer and waits for its pipeline outcome, which is
+    /// dispatched from the event subscription shared by all blocking submits of the client.
+    /// Gives [`SubmitBlockingStatus::TimedOut`] if there is no outcome within the status timeout.
+    pub async fn submit_transaction_blocking_to(
+        &self,
+        peer: &Peer,
+        transaction: SignedTransaction,
+    ) -> Result<SubmitBlockingStatus> {
+        self.submit_transaction_blocking_with(peer, transaction, || ())
             .await
-            .ok_or_else(|| eyre!("Unexpected closing channel"))?;
-        Ok(res)
+    }
+
+    /// Submits the transaction to the peer and waits for its outcome, calling `on_submit`
+    /// once the event listener is set up, right before the transaction is sent.
+    pub async fn submit_transaction_blocking_with(
+        &self,
+        peer: &Peer,
+        transaction: SignedTransaction,
+        on_submit: impl FnOnce() + Send,
+    ) -> Result<SubmitBlockingStatus> {
+        let hash = transaction.hash().into();
+        let receiver = self.events.wait_for(&self.iroha_client, hash).await?;
+        on_submit();
+        if let Err(err) = self.submit_transaction_to(peer, transaction).await {
+            self.events.forget(&hash);
+            return Err(err);
+        }
+        match time::timeout(self.status_timeout, receiver).await {
+            Ok(status) => status.wrap_err("Event listener dropped the transaction"),
+            Err(_) => {
+                self.events.forget(&hash);
+                Ok(SubmitBlockingStatus::TimedOut)
+            }
+        }
     }
 }
 
-#[derive(Debug)]
-pub enum SubmitBlockingStatus {
-    Committed(HashOf<SignedTransaction>),
-    Rejected(PipelineRejectionReason),
-    Unknown,
+/// Transaction found in a committed block.
+#[derive(Debug, Clone)]
+pub struct BlockTransaction {
+    pub hash: Hash,
+    /// Reason of the rejection if the transaction is rejected.
+    pub rejection: Option<PipelineRejectionReason>,
 }
 
-impl From<IrohaClient> for Client {
-    fn from(iroha_client: IrohaClient) -> Self {
-        Self {
-            iroha_client,
-            hyper_client: HyperClient::new(),
-        }
-    }
+/// Transaction of a run found in a committed block.
+#[derive(Debug, Clone)]
+pub struct RunTransaction {
+    pub hash: Hash,
+    pub is_rejected: bool,
+    pub operation: Option<String>,
+    pub sent_at_ms: Option<u64>,
+    /// Timestamp of the block, which is set when the block is created.
+    pub committed_at_ms: u64,
 }
 
-impl From<(IrohaClient, HyperClient<HttpConnector>)> for Client {
-    fn from((iroha_client, hyper_client): (IrohaClient, HyperClient<HttpConnector>)) -> Self {
-        Self {
-            iroha_client,
-            hyper_client,
-        }
-    }
+pub(super) fn metadata_key(key: &str) -> Name {
+    Name::from_str(key).expect("Failed to create a metadata key")
+}
+
+#[derive(Debug)]
+pub enum SubmitBlockingStatus {
+    Committed(Hash),
+    Rejected(PipelineRejectionReason),
+    Unknown,
+    TimedOut,
 }
@@ -0,0 +1,197 @@
+use super::client::SubmitBlockingStatus;
+use color_eyre::eyre::{Result, WrapErr as _};
+use futures_util::stream::StreamExt;
+use iroha_client::client::{events_api::AsyncEventStream, Client as IrohaClient};
+use iroha_crypto::Hash;
+use iroha_data_model::prelude::*;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    spawn,
+    sync::{oneshot, Mutex as AsyncMutex},
+};
+use tracing::{debug, warn};
+
+/// Single pipeline event subscription shared by all blocking submits of a client.
+/// Events are dispatched to the waiters by transaction hash.
+#[derive(Debug, Default)]
+pub struct EventMultiplexer {
+    waiters: Mutex<Waiters>,
+    /// Serializes establishing of the subscription.
+    connecting: AsyncMutex<()>,
+}
+
+#[derive(Debug, Default)]
+struct Waiters {
+    is_listening: bool,
+    senders: HashMap<Hash, oneshot::Sender<SubmitBlockingStatus>>,
+}
+
+impl EventMultiplexer {
+    /// Registers a waiter for the pipeline outcome of the transaction, subscribing
+    /// to events first if there is no subscription yet.
+    ///
+    /// # Errors
+    /// if the subscription can't be established.
+    pub async fn wait_for(
+        self: &Arc<Self>,
+        iroha_client: &IrohaClient,
+        hash: Hash,
+    ) -> Result<oneshot::Receiver<SubmitBlockingStatus>> {
+        loop {
+            if let Some(receiver) = self.register(hash) {
+                return Ok(receiver);
+            }
+            self.listen(iroha_client).await?;
+        }
+    }
+
+    /// Registers a waiter for the transaction if there is a subscription.
+    fn register(&self, hash: Hash) -> Option<oneshot::Receiver<SubmitBlockingStatus>> {
+        let mut waiters = self.waiters.lock().expect("Failed to lock waiters");
+        if !waiters.is_listening {
+            return None;
+        }
+        let (sender, receiver) = oneshot::channel();
+        waiters.senders.insert(hash, sender);
+        Some(receiver)
+    }
+
+    /// Drops the waiter of the transaction, e.g. if it failed to be submitted.
+    pub fn forget(&self, hash: &Hash) {
+        self.waiters
+            .lock()
+            .expect("Failed to lock waiters")
+            .senders
+            .remove(hash);
+    }
+
+    async fn listen(self: &Arc<Self>, iroha_client: &IrohaClient) -> Result<()> {
+        let _connecting = self.connecting.lock().await;
+        if self
+            .waiters
+            .lock()
+            .expect("Failed to lock waiters")
+            .is_listening
+        {
+            return Ok(());
+        }
+        let event_stream = iroha_client
+            .listen_for_events_async(PipelineEventFilter::new().into())
+            .await
+            .wrap_err("Failed to establish event listener connection")?;
+        self.waiters
+            .lock()
+            .expect("Failed to lock waiters")
+            .is_listening = true;
+        spawn(Arc::clone(self).dispatch(event_stream));
+        Ok(())
+    }
+
+    async fn dispatch(self: Arc<Self>, mut event_stream: AsyncEventStream) {
+        while let Some(event) = event_stream.next().await {
+            let event = match event {
+                Ok(Event::Pipeline(event)) => event,
+                Ok(_) => continue,
+                Err(err) => {
+                    warn!("Event stream failed: {}", err);
+                    break;
+                }
+            };
+            if !matches!(event.entity_kind(), PipelineEntityKind::Transaction) {
+                continue;
+            }
+            let status = match event.status() {
+                PipelineStatus::Validating => continue,
+                PipelineStatus::Rejected(reason) => SubmitBlockingStatus::Rejected(reason.clone()),
+                PipelineStatus::Committed => SubmitBlockingStatus::Committed(*event.hash()),
+            };
+            self.outcome_is_known(event.hash(), status);
+        }
+        debug!("Event stream is closed");
+        self.stream_is_closed();
+    }
+
+    /// Passes the outcome to the waiter of the transaction, if there is one.
+    fn outcome_is_known(&self, hash: &Hash, status: SubmitBlockingStatus) {
+        let sender = self
+            .waiters
+            .lock()
+            .expect("Failed to lock waiters")
+            .senders
+            .remove(hash);
+        if let Some(sender) = sender {
+            // The waiter may have given up already
+            let _ = sender.send(status);
+        }
+    }
+
+    /// Tells all waiters that their outcomes are unknown, so the next waiter subscribes again.
+    fn stream_is_closed(&self) {
+        let mut waiters = self.waiters.lock().expect("Failed to lock waiters");
+        waiters.is_listening = false;
+        for (_, sender) in waiters.senders.drain() {
+            let _ = sender.send(SubmitBlockingStatus::Unknown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> Hash {
+        Hash::new([byte])
+    }
+
+    fn listening() -> EventMultiplexer {
+        let events = EventMultiplexer::default();
+        events
+            .waiters
+            .lock()
+            .expect("Failed to lock waiters")
+            .is_listening = true;
+        events
+    }
+
+    #[test]
+    fn no_waiters_without_a_subscription() {
+        assert!(EventMultiplexer::default().register(hash(1)).is_none());
+    }
+
+    #[test]
+    fn outcomes_are_dispatched_by_hash() {
+        let events = listening();
+        let mut first = events.register(hash(1)).expect("Subscribed");
+        let mut second = events.register(hash(2)).expect("Subscribed");
+        events.outcome_is_known(&hash(2), SubmitBlockingStatus::Committed(hash(2)));
+        events.outcome_is_known(&hash(3), SubmitBlockingStatus::Committed(hash(3)));
+        assert!(matches!(
+            second.try_recv(),
+            Ok(SubmitBlockingStatus::Committed(committed)) if committed == hash(2)
+        ));
+        assert!(first.try_recv().is_err());
+        events.forget(&hash(1));
+        assert!(first.try_recv().is_err());
+        assert!(events
+            .waiters
+            .lock()
+            .expect("Failed to lock waiters")
+            .senders
+            .is_empty());
+    }
+
+    #[test]
+    fn waiters_get_unknown_when_the_stream_closes() {
+        let events = listening();
+        let mut waiter = events.register(hash(1)).expect("Subscribed");
+        events.stream_is_closed();
+        assert!(matches!(
+            waiter.try_recv(),
+            Ok(SubmitBlockingStatus::Unknown)
+        ));
+        assert!(events.register(hash(2)).is_none());
+    }
+}
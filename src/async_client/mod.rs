@@ -1,5 +1,8 @@
 mod client;
+mod events;
 mod http;
+mod peers;
 
-pub use client::{Client, SubmitBlockingStatus};
+pub use client::{BlockTransaction, Client, RunTransaction, SubmitBlockingStatus};
 pub use http::{AsyncRequest, AsyncRequestBuilder};
+pub use peers::SubmitStrategy;
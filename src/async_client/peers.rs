@@ -0,0 +1,360 @@
+use super::client::metadata_key;
+use crate::{config::PeerEndpoint, run_id::METADATA_OPERATION};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{bail, eyre, Result, WrapErr as _};
+use iroha_client::client::Client as IrohaClient;
+use iroha_config::client::Configuration;
+use iroha_data_model::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::*};
+use serde::Serialize;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash as _, Hasher as _},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+use strum_macros::{Display, EnumString};
+use tracing::{info, warn};
+use url::Url;
+
+/// Consecutive failed submissions after which a peer is taken out of rotation
+/// until it passes a health probe.
+const FAILURES_TO_EJECT: usize = 3;
+
+/// How the peer to submit a transaction to is chosen.
+#[derive(Debug, Clone, Copy, Default, EnumString, Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum SubmitStrategy {
+    /// Peers in turn.
+    #[default]
+    RoundRobin,
+    /// Uniformly random peer.
+    Random,
+    /// Random peer in proportion to its weight.
+    Weighted,
+    /// The same peer for all transactions of an operation. All transactions are signed
+    /// by the same account, so the operation is what tells them apart.
+    Sticky,
+}
+
+/// Peer which transactions can be submitted to.
+#[derive(Debug)]
+pub struct Peer {
+    name: String,
+    url: Url,
+    weight: f64,
+    pub(super) iroha_client: IrohaClient,
+    health: Mutex<Health>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: usize,
+    /// Set while the peer is out of rotation.
+    down_since: Option<DateTime<Utc>>,
+}
+
+/// Health of a peer as reported over HTTP.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerHealth {
+    pub peer: String,
+    pub is_healthy: bool,
+    pub consecutive_failures: usize,
+    pub down_since: Option<DateTime<Utc>>,
+}
+
+impl Peer {
+    /// Torii URL of the peer, which identifies it in the status.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// URL of the Torii health endpoint of the peer.
+    pub fn health_url(&self) -> Result<Url> {
+        health_url(&self.url)
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health
+            .lock()
+            .expect("Failed to lock peer health")
+            .down_since
+            .is_none()
+    }
+
+    pub fn health(&self) -> PeerHealth {
+        let health = self.health.lock().expect("Failed to lock peer health");
+        PeerHealth {
+            peer: self.name.clone(),
+            is_healthy: health.down_since.is_none(),
+            consecutive_failures: health.consecutive_failures,
+            down_since: health.down_since,
+        }
+    }
+
+    /// Takes the peer out of rotation after too many consecutive failures.
+    pub fn submit_failed(&self) {
+        let mut health = self.health.lock().expect("Failed to lock peer health");
+        if health.submit_failed() {
+            warn!(
+                peer = %self.name,
+                failures = health.consecutive_failures,
+                "Taking the peer out of rotation"
+            );
+        }
+    }
+
+    /// Brings the peer back into rotation if it was out of it.
+    pub fn is_responding(&self) {
+        let mut health = self.health.lock().expect("Failed to lock peer health");
+        if health.is_responding() {
+            info!(peer = %self.name, "Bringing the peer back into rotation");
+        }
+    }
+}
+
+impl Health {
+    /// Counts a failed submission. Returns `true` if the peer is taken out of rotation by it.
+    fn submit_failed(&mut self) -> bool {
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= FAILURES_TO_EJECT && self.down_since.is_none() {
+            self.down_since = Some(Utc::now());
+            return true;
+        }
+        false
+    }
+
+    /// Resets the failures. Returns `true` if the peer is brought back into rotation by it.
+    fn is_responding(&mut self) -> bool {
+        self.consecutive_failures = 0;
+        self.down_since.take().is_some()
+    }
+}
+
+/// Peers of the network together with the strategy of choosing among them.
+#[derive(Debug)]
+pub struct Peers {
+    peers: Vec<Peer>,
+    strategy: SubmitStrategy,
+    next: AtomicUsize,
+}
+
+impl Peers {
+    /// Makes a client for every endpoint from the configuration with its Torii URL replaced.
+    ///
+    /// # Errors
+    /// if there are no endpoints or a client can't be made.
+    pub fn new(
+        config: &Configuration,
+        endpoints: &[PeerEndpoint],
+        strategy: SubmitStrategy,
+    ) -> Result<Self> {
+        if endpoints.is_empty() {
+            bail!("No peers to submit transactions to");
+        }
+        let peers = endpoints
+            .iter()
+            .map(|endpoint| {
+                let mut config = config.clone();
+                config.torii_api_url = endpoint.url.clone();
+                Ok(Peer {
+                    name: endpoint.url.to_string(),
+                    url: endpoint.url.clone(),
+                    weight: endpoint.weight.into(),
+                    iroha_client: IrohaClient::new(&config).wrap_err_with(|| {
+                        format!("Failed to make a client for peer {}", endpoint.url)
+                    })?,
+                    health: Mutex::default(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            peers,
+            strategy,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// The first configured peer, which is used for everything except submissions.
+    pub fn first(&self) -> &Peer {
+        &self.peers[0]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Peer> {
+        self.peers.iter()
+    }
+
+    /// Chooses the peer to submit the transaction to according to the strategy among
+    /// the healthy ones, or among all of them if none is healthy.
+    pub fn choose(&self, transaction: &SignedTransaction) -> &Peer {
+        let candidates = healthy_or_all(&self.peers, Peer::is_healthy);
+        let operation = match transaction
+            .payload()
+            .metadata
+            .get(&metadata_key(METADATA_OPERATION))
+        {
+            Some(Value::String(operation)) => Some(operation.as_str()),
+            _ => None,
+        };
+        let weights = candidates
+            .iter()
+            .map(|peer| peer.weight)
+            .collect::<Vec<_>>();
+        candidates[self.choose_index(&weights, operation, &mut thread_rng())]
+    }
+
+    /// Chooses the index of a candidate with the given weights. Sticky choices are keyed
+    /// on the operation of the transaction.
+    fn choose_index(&self, weights: &[f64], operation: Option<&str>, rng: &mut impl Rng) -> usize {
+        let index = match self.strategy {
+            SubmitStrategy::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed),
+            SubmitStrategy::Random => rng.gen_range(0..weights.len()),
+            SubmitStrategy::Weighted => WeightedIndex::new(weights)
+                .expect("Weights are positive")
+                .sample(rng),
+            SubmitStrategy::Sticky => {
+                let mut hasher = DefaultHasher::new();
+                operation.hash(&mut hasher);
+                hasher.finish() as usize
+            }
+        };
+        index % weights.len()
+    }
+}
+
+/// Returns the healthy items, or all of them if none is healthy.
+fn healthy_or_all<T>(items: &[T], is_healthy: impl Fn(&T) -> bool) -> Vec<&T> {
+    let healthy = items
+        .iter()
+        .filter(|item| is_healthy(item))
+        .collect::<Vec<_>>();
+    if healthy.is_empty() {
+        items.iter().collect()
+    } else {
+        healthy
+    }
+}
+
+/// Appends the `health` segment to the Torii URL, keeping its path if Torii is served under one.
+fn health_url(url: &Url) -> Result<Url> {
+    let mut health_url = url.clone();
+    health_url
+        .path_segments_mut()
+        .map_err(|()| eyre!("Failed to make the health URL of {}", url))?
+        .pop_if_empty()
+        .push("health");
+    Ok(health_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health_url_of(url: &str) -> String {
+        health_url(&Url::parse(url).expect("Valid URL"))
+            .expect("Health URL")
+            .to_string()
+    }
+
+    #[test]
+    fn health_url_keeps_the_path() {
+        assert_eq!(
+            health_url_of("http://127.0.0.1:8080"),
+            "http://127.0.0.1:8080/health"
+        );
+        assert_eq!(
+            health_url_of("http://127.0.0.1:8080/"),
+            "http://127.0.0.1:8080/health"
+        );
+        assert_eq!(
+            health_url_of("http://10.0.0.2/peer0"),
+            "http://10.0.0.2/peer0/health"
+        );
+        assert_eq!(
+            health_url_of("http://10.0.0.2/peer0/"),
+            "http://10.0.0.2/peer0/health"
+        );
+    }
+
+    fn peers(strategy: SubmitStrategy) -> Peers {
+        Peers {
+            peers: Vec::new(),
+            strategy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn choices(strategy: SubmitStrategy, weights: &[f64], operation: Option<&str>) -> Vec<usize> {
+        let peers = peers(strategy);
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..1000)
+            .map(|_| peers.choose_index(weights, operation, &mut rng))
+            .collect()
+    }
+
+    fn shares(choices: &[usize], candidates: usize) -> Vec<usize> {
+        let mut shares = vec![0; candidates];
+        for choice in choices {
+            shares[*choice] += 1;
+        }
+        shares
+    }
+
+    #[test]
+    fn round_robin_takes_peers_in_turn() {
+        assert_eq!(
+            choices(SubmitStrategy::RoundRobin, &[1.0; 3], None)[..6],
+            [0, 1, 2, 0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn random_uses_every_peer() {
+        let shares = shares(&choices(SubmitStrategy::Random, &[1.0; 3], None), 3);
+        assert!(shares.iter().all(|share| *share > 250), "{:?}", shares);
+    }
+
+    #[test]
+    fn weighted_follows_weights() {
+        let shares = shares(&choices(SubmitStrategy::Weighted, &[1.0, 3.0], None), 2);
+        assert!((200..300).contains(&shares[0]), "{:?}", shares);
+    }
+
+    #[test]
+    fn sticky_keeps_an_operation_on_one_peer() {
+        for operation in [Some("RegisterDomain"), Some("RegisterAccount"), None] {
+            let choices = choices(SubmitStrategy::Sticky, &[1.0; 3], operation);
+            assert!(choices.iter().all(|choice| *choice == choices[0]));
+        }
+    }
+
+    #[test]
+    fn falls_back_to_all_peers_if_none_is_healthy() {
+        assert_eq!(
+            healthy_or_all(&[true, false, true], |healthy| *healthy).len(),
+            2
+        );
+        assert_eq!(healthy_or_all(&[false, false], |healthy| *healthy).len(), 2);
+    }
+
+    #[test]
+    fn peer_is_ejected_after_consecutive_failures_and_restored() {
+        let mut health = Health::default();
+        for _ in 1..FAILURES_TO_EJECT {
+            assert!(!health.submit_failed());
+        }
+        assert!(!health.is_responding());
+        assert!(health.down_since.is_none());
+        for _ in 1..FAILURES_TO_EJECT {
+            assert!(!health.submit_failed());
+        }
+        assert!(health.submit_failed());
+        assert!(health.down_since.is_some());
+        assert!(!health.submit_failed());
+        assert!(health.is_responding());
+        assert_eq!(health.consecutive_failures, 0);
+        assert!(health.down_since.is_none());
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    control::OperationSettings,
+    operation::Operation,
+    status::{InFlight, Status},
+};
+use color_eyre::eyre::{Result, WrapErr as _};
+use iroha_crypto::Hash;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// State of a run saved to a file, so a restarted daemon can continue it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub status: Status,
+    /// Operations left to perform.
+    pub operations: BTreeMap<Operation, OperationSettings>,
+    /// Index used to name entities of the next operation.
+    pub next_index: usize,
+    /// Sequence number of the next sent transaction.
+    #[serde(default)]
+    pub next_sequence: u64,
+    /// Sent transactions which were waiting for their outcome.
+    #[serde(default)]
+    pub in_flight: Vec<(Hash, InFlight)>,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint next to the file and renames it over, so an interrupted
+    /// write never leaves a broken state file behind.
+    ///
+    /// # Errors
+    /// if the file can't be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let temporary = path.with_extension("tmp");
+        let contents = serde_json::to_vec(self).wrap_err("Failed to serialize the checkpoint")?;
+        fs::write(&temporary, contents).wrap_err_with(|| {
+            format!(
+                "Failed to write the checkpoint to `{}`",
+                temporary.display()
+            )
+        })?;
+        fs::rename(&temporary, path)
+            .wrap_err_with(|| format!("Failed to replace the state file `{}`", path.display()))
+    }
+
+    /// # Errors
+    /// if the file can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read(path)
+            .wrap_err_with(|| format!("Failed to read the state file `{}`", path.display()))?;
+        serde_json::from_slice(&contents)
+            .wrap_err_with(|| format!("Failed to parse the state file `{}`", path.display()))
+    }
+}
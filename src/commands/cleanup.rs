@@ -0,0 +1,166 @@
+use crate::{
+    args::RunArgs,
+    async_client::{Client, SubmitBlockingStatus, SubmitStrategy},
+    config::load_configuration,
+    number::PositiveFloat,
+    run_id::RunId,
+};
+use async_trait::async_trait;
+use color_eyre::eyre::{Result, WrapErr as _};
+use iroha_data_model::prelude::*;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use structopt::StructOpt;
+use tokio::{task, time};
+use tracing::{info, warn};
+
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    #[structopt(long, default_value = "config.json")]
+    config: PathBuf,
+    /// Run whose entities are unregistered
+    #[structopt(long)]
+    run_id: RunId,
+    /// Rate of unregister transactions
+    #[structopt(short = "t", long, default_value = "10.0")]
+    tps: PositiveFloat,
+    /// Only report the entities of the run without unregistering them
+    #[structopt(long)]
+    dry_run: bool,
+}
+
+/// Entities of a run by kind, in the order they are unregistered: asset definitions
+/// (together with their assets) and accounts go before the domains they are in.
+const KINDS: [&str; 3] = ["asset_definitions", "accounts", "domains"];
+
+/// Outcome of unregistering entities of a kind.
+#[derive(Debug, Default, Serialize)]
+struct KindReport {
+    found: usize,
+    removed: Vec<String>,
+    rejected: Vec<String>,
+    /// Entities whose unregistration failed to be submitted or got no outcome.
+    failed: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CleanupReport {
+    run_id: RunId,
+    dry_run: bool,
+    entities: BTreeMap<&'static str, KindReport>,
+}
+
+#[async_trait]
+impl RunArgs for Args {
+    async fn run<T: Write + Send>(self, writer: &mut std::io::BufWriter<T>) -> Result<()> {
+        let report = cleanup(self).await?;
+        writeln!(writer, "{}", serde_json::to_string_pretty(&report)?)
+            .wrap_err("Failed to pretty print a result")?;
+        Ok(())
+    }
+}
+
+async fn cleanup(args: Args) -> Result<CleanupReport> {
+    let (cfg, peers) = load_configuration(&args.config)?;
+    let client = Client::new(&cfg, &peers, SubmitStrategy::default())?;
+    let entities = find_run_entities(&client, args.run_id.clone()).await?;
+    let mut report = CleanupReport {
+        run_id: args.run_id,
+        dry_run: args.dry_run,
+        entities: BTreeMap::new(),
+    };
+    let period = Duration::from_secs_f64(1.0 / f64::from(args.tps));
+    for (kind, ids) in KINDS.into_iter().zip(entities) {
+        info!(kind, found = ids.len(), "Found entities of the run");
+        let kind_report = Arc::new(Mutex::new(KindReport {
+            found: ids.len(),
+            ..KindReport::default()
+        }));
+        if !args.dry_run {
+            // Kinds go one after another, so entities are gone before their domains
+            let mut interval = time::interval(period);
+            let mut handles = Vec::with_capacity(ids.len());
+            for id in ids {
+                interval.tick().await;
+                let client = client.clone();
+                let kind_report = Arc::clone(&kind_report);
+                handles.push(task::spawn(async move {
+                    let name = id.to_string();
+                    let outcome = client.submit_blocking(UnregisterExpr::new(id)).await;
+                    let mut kind_report = kind_report.lock().expect("Failed to lock the report");
+                    match outcome {
+                        Ok(SubmitBlockingStatus::Committed(_)) => kind_report.removed.push(name),
+                        Ok(SubmitBlockingStatus::Rejected(reason)) => {
+                            warn!(entity = %name, "Unregistration is rejected: {}", reason);
+                            kind_report.rejected.push(name);
+                        }
+                        Ok(SubmitBlockingStatus::Unknown | SubmitBlockingStatus::TimedOut) => {
+                            kind_report.failed.push(name)
+                        }
+                        Err(err) => {
+                            warn!(entity = %name, "Failed to unregister: {}", err);
+                            kind_report.failed.push(name);
+                        }
+                    }
+                }));
+            }
+            for handle in handles {
+                handle.await.expect("Failed to handle a spawned task");
+            }
+        }
+        let kind_report = Arc::try_unwrap(kind_report)
+            .expect("All tasks are finished")
+            .into_inner()
+            .expect("Failed to lock the report");
+        report.entities.insert(kind, kind_report);
+    }
+    Ok(report)
+}
+
+/// Finds entities whose names were made by the run, grouped in the order of [`KINDS`].
+async fn find_run_entities(client: &Client, run_id: RunId) -> Result<[Vec<IdBox>; 3]> {
+    client
+        .query(move |iroha_client| {
+            let mut asset_definitions = Vec::new();
+            for definition in iroha_client
+                .request(FindAllAssetsDefinitions)
+                .wrap_err("Failed to query asset definitions")?
+            {
+                let id = definition
+                    .wrap_err("Failed to fetch an asset definition")?
+                    .id()
+                    .clone();
+                if run_id.owns(&id.name.to_string()) {
+                    asset_definitions.push(IdBox::AssetDefinitionId(id));
+                }
+            }
+            let mut accounts = Vec::new();
+            for account in iroha_client
+                .request(FindAllAccounts)
+                .wrap_err("Failed to query accounts")?
+            {
+                let id = account.wrap_err("Failed to fetch an account")?.id().clone();
+                if run_id.owns(&id.name.to_string()) {
+                    accounts.push(IdBox::AccountId(id));
+                }
+            }
+            let mut domains = Vec::new();
+            for domain in iroha_client
+                .request(FindAllDomains)
+                .wrap_err("Failed to query domains")?
+            {
+                let id = domain.wrap_err("Failed to fetch a domain")?.id().clone();
+                if run_id.owns(&id.name.to_string()) {
+                    domains.push(IdBox::DomainId(id));
+                }
+            }
+            Ok([asset_definitions, accounts, domains])
+        })
+        .await
+}
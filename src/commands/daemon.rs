@@ -1,59 +1,161 @@
-use super::make_instruction_by_operation;
-use crate::{args::RunArgs, number::PositiveFloat, operation::Operation, status::Status};
+use super::{make_instruction_by_operation, make_run_metadata, probe_unhealthy_peers, SubmitArgs};
+use crate::{
+    args::RunArgs,
+    arrival::Arrival,
+    async_client::Client,
+    checkpoint::Checkpoint,
+    config::{load_configuration, status_timeout},
+    control::{LoadSettings, NextAction, OperationSettings},
+    history::{self, History},
+    load_shape::LoadShape,
+    metrics::Encoder,
+    number::PositiveFloat,
+    operation::{Operation, OperationArg},
+    reconcile::{reconcile, CLOCK_SKEW_SECS},
+    run_id::RunId,
+    scenario::{drive_scenario, Scenario},
+    status::Status,
+};
 use async_trait::async_trait;
-use color_eyre::eyre::Result;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{bail, eyre, Result};
 use futures_util::StreamExt;
 use hyper::{
     header,
     service::{make_service_fn, service_fn},
-    Body, Request, Response, Server,
+    Body, Method, Request, Response, Server, StatusCode,
 };
-use iroha_client::client::Client;
-use iroha_config::client::Configuration;
 use iroha_data_model::{isi::InstructionExpr, prelude::*};
+use serde::Serialize;
 use std::{
-    collections::HashMap,
-    fs::File,
+    collections::BTreeMap,
+    fmt::Display,
     io::Write,
     net::SocketAddr,
+    num::{NonZeroU64, NonZeroUsize},
+    path::PathBuf,
     str::FromStr,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, RwLock,
-    },
-    thread,
+    sync::{Arc, RwLock},
     time::{Duration, Instant},
 };
 use structopt::StructOpt;
-use tokio::{join, select, signal, sync::Notify, task};
+use tokio::{
+    join, select, signal,
+    sync::{Notify, Semaphore},
+    task, time,
+};
 use tracing::{debug, info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
+use url::form_urlencoded;
+
+/// Delay before the first attempt to restore a lost event stream, doubled on every failure.
+const MIN_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
 
+/// Arguments shared by commands running as a daemon.
 #[derive(Debug, StructOpt)]
-pub struct Args {
-    #[structopt(short = "a", long, default_value = "127.0.0.1:8084")]
+pub struct ServeArgs {
+    #[structopt(short = "a", long, env = "ADDRESS", default_value = "127.0.0.1:8084")]
     address: SocketAddr,
+    /// Overrides the port of the address
+    #[structopt(short = "p", long, env = "PORT")]
+    port: Option<u16>,
+    #[structopt(long, default_value = "config.json")]
+    config: PathBuf,
+    /// Maximum amount of submissions waiting for a response from Torii
+    #[structopt(long, default_value = "256")]
+    max_in_flight: NonZeroUsize,
+    #[structopt(flatten)]
+    submit: SubmitArgs,
+    /// Recover outcomes of sent transactions from blocks committed while the event stream was lost
+    #[structopt(long)]
+    reconcile_gaps: bool,
+    /// Scan the blocks for the transactions of the run on shutdown and log how they
+    /// compare with the live counters
+    #[structopt(long)]
+    reconcile_on_shutdown: bool,
+    /// Interval between snapshots of the status kept in the history
+    #[structopt(long, default_value = "60")]
+    history_interval_secs: NonZeroU64,
+    /// Amount of the latest snapshots kept in the history
+    #[structopt(long, default_value = "1440")]
+    history_size: NonZeroUsize,
+    /// File to append snapshots to as JSON lines, loaded on start
+    #[structopt(long)]
+    history_file: Option<PathBuf>,
+    /// File to periodically save the status and the progress of operations to
+    #[structopt(long)]
+    state_file: Option<PathBuf>,
+    /// Interval between saves to the state file
+    #[structopt(long, default_value = "30")]
+    checkpoint_interval_secs: NonZeroU64,
+    /// Continue the run saved in the state file instead of starting from scratch,
+    /// not supported for scenarios
+    #[structopt(long, requires = "state_file")]
+    resume: bool,
+}
+
+impl ServeArgs {
+    fn address(&self) -> SocketAddr {
+        let mut address = self.address;
+        if let Some(port) = self.port {
+            address.set_port(port);
+        }
+        address
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    #[structopt(flatten)]
+    serve: ServeArgs,
     #[structopt(short = "t", long, default_value = "2.0")]
     tps: PositiveFloat,
+    /// Time-varying rate overriding `--tps`: `constant:TPS`, `ramp:FROM:TO:DURATION_SECS`,
+    /// `steps:FROM:TO:STEPS:STEP_SECS`, `sine:MEAN:AMPLITUDE:PERIOD_SECS` or
+    /// `spike:BASE:PEAK:PERIOD_SECS:SPIKE_SECS`
+    #[structopt(long)]
+    shape: Option<LoadShape>,
+    /// Distribution of intervals between transactions: `fixed`, `poisson` or `bursty:SIZE`
+    #[structopt(long, default_value = "fixed")]
+    arrival: Arrival,
+    /// Seed of the generator driving intervals and the choice of operations, random if absent
+    #[structopt(long)]
+    seed: Option<u64>,
+    /// Amount of transactions for operations without their own count
     #[structopt(short = "c", long, default_value = "100")]
     count: usize,
+    /// Operation to perform as `NAME[:WEIGHT[:COUNT]]`, where `COUNT` is a number or `unlimited`
     #[structopt(short = "o", long, required = true)]
-    operation: Vec<Operation>,
+    operation: Vec<OperationArg>,
 }
 
 #[async_trait]
 impl RunArgs for Args {
     async fn run<T: Write + Send>(self, _writer: &mut std::io::BufWriter<T>) -> Result<()> {
-        run_daemon(self.address, self.tps, self.count, self.operation).await
+        let mut operations = BTreeMap::new();
+        for arg in &self.operation {
+            if operations
+                .insert(arg.operation, arg.settings(self.count))
+                .is_some()
+            {
+                bail!("Operation {:?} is given more than once", arg.operation);
+            }
+        }
+        let shape = self.shape.unwrap_or_else(|| self.tps.into());
+        let settings = LoadSettings::new(shape, self.arrival, self.seed, operations);
+        run_daemon(self.serve, settings, None).await
     }
 }
 
-async fn run_daemon(
-    address: SocketAddr,
-    tps: PositiveFloat,
-    count: usize,
-    operations: Vec<Operation>,
+/// Runs the load with the given settings and serves its status until a shutdown signal.
+/// If a scenario is given, its stages drive the settings.
+pub async fn run_daemon(
+    serve: ServeArgs,
+    mut settings: LoadSettings,
+    scenario: Option<Scenario>,
 ) -> Result<()> {
+    let address = serve.address();
     info!("Welcome to the Iroha 2 longevity load script");
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::DEBUG)
@@ -61,61 +163,131 @@ async fn run_daemon(
     tracing::subscriber::set_global_default(subscriber).expect("Failed to init logging");
     info!("Staring load script");
     info!("Reading config file");
-    let config_file = File::open("config.json").expect("`config.json` not found.");
-    let cfg: Configuration =
-        serde_json::from_reader(config_file).expect("Failed to deserialize configuration.");
+    let (cfg, peers) = load_configuration(&serve.config)?;
     warn!("No status updates are given in the logs. To access that information please use `curl -X GET {} -i", address);
     info!("Reading configuration finished");
     debug!("Configuration: {:#?}", cfg);
-    let operations = operations.into_iter().fold(HashMap::new(), |mut m, op| {
-        m.insert(op, count);
-        m
-    });
-    let shared_client = Client::new(&cfg)?;
+    if serve.resume && scenario.is_some() {
+        bail!("A scenario can't be resumed, as it would start again from its first stage");
+    }
+    let new_run_id = || serve.submit.run_id.clone().unwrap_or_else(RunId::generate);
+    let status = match &serve.state_file {
+        Some(path) if serve.resume && path.exists() => {
+            info!("Resuming the run from `{}`", path.display());
+            let checkpoint = Checkpoint::load(path)?;
+            settings.restore_progress(
+                checkpoint.operations,
+                checkpoint.next_index,
+                checkpoint.next_sequence,
+            );
+            let mut status = checkpoint.status;
+            status.in_flight_is_lost(checkpoint.in_flight);
+            status
+        }
+        Some(path) if serve.resume => {
+            warn!("No state file `{}` to resume from", path.display());
+            Status::for_run(new_run_id())
+        }
+        _ => Status::for_run(new_run_id()),
+    };
+    let run_id = status
+        .run_id()
+        .cloned()
+        .ok_or_else(|| eyre!("The state file has no run id"))?;
+    if matches!(&serve.submit.run_id, Some(given) if *given != run_id) {
+        warn!("Ignoring the given run id, the resumed run is {}", run_id);
+    }
+    info!(run_id = %run_id, "Run id");
+    let shared_settings = Arc::new(RwLock::new(settings));
+    let shared_history = Arc::new(RwLock::new(History::new(
+        serve.history_size.get(),
+        serve.history_file.clone(),
+    )?));
+    let shared_client = Client::new(&cfg, &peers, serve.submit.submit_strategy)?;
     let client = shared_client.clone();
     let notify_close = Arc::new(Notify::new());
-    let shared_status = Arc::new(RwLock::new(Status::default()));
+    let shared_status = Arc::new(RwLock::new(status));
     let status = Arc::clone(&shared_status);
     info!("Spawning clients");
     let update_status_fut = task::spawn(update_status_according_to_events(
         client,
         status,
+        serve.reconcile_gaps,
         Arc::clone(&notify_close),
     ));
     info!("First client thread spawned");
-    let client = shared_client;
+    let client = shared_client.clone();
     let status = Arc::clone(&shared_status);
-    let notify_close_clone = Arc::clone(&notify_close);
-    let perform_operations_fut = task::spawn_blocking(move || {
-        let interval = Duration::from_secs_f64(1_f64 / f64::from(tps));
-        let is_closed = Arc::new(AtomicBool::new(false));
-        let is_closed_clone = Arc::clone(&is_closed);
-        task::spawn(async move {
-            notify_close_clone.notified().await;
-            is_closed_clone.store(true, Ordering::SeqCst);
-        });
-        perform_operations(
-            client.clone(),
-            Arc::clone(&status),
-            interval,
-            operations,
-            Arc::clone(&is_closed),
-        );
-        submit_empty_transactions(client, status, interval, is_closed);
+    let settings = Arc::clone(&shared_settings);
+    let perform_operations_fut = task::spawn(perform_operations(
+        client,
+        status,
+        settings,
+        run_id.clone(),
+        serve.max_in_flight.get(),
+        Arc::clone(&notify_close),
+    ));
+    info!("Second thread is spawned");
+    let checkpoint_fut = serve.state_file.clone().map(|path| {
+        task::spawn(save_checkpoints(
+            Arc::clone(&shared_status),
+            Arc::clone(&shared_settings),
+            path,
+            Duration::from_secs(serve.checkpoint_interval_secs.get()),
+            Arc::clone(&notify_close),
+        ))
     });
-    info!("Second thread is spawned. Starting server");
+    task::spawn(record_history(
+        Arc::clone(&shared_status),
+        Arc::clone(&shared_settings),
+        Arc::clone(&shared_history),
+        Duration::from_secs(serve.history_interval_secs.get()),
+        Arc::clone(&notify_close),
+    ));
+    task::spawn(probe_unhealthy_peers(
+        shared_client.clone(),
+        Arc::clone(&notify_close),
+    ));
+    task::spawn(expire_in_flight(
+        Arc::clone(&shared_status),
+        status_timeout(&cfg, serve.submit.status_timeout_ms),
+        Arc::clone(&notify_close),
+    ));
+    if let Some(scenario) = scenario {
+        task::spawn(drive_scenario(
+            scenario,
+            Arc::clone(&shared_settings),
+            Arc::clone(&notify_close),
+        ));
+    }
+    let final_client = shared_client.clone();
+    let final_status = Arc::clone(&shared_status);
+    info!("Starting server");
     let service = make_service_fn(move |_conn| {
+        let client = shared_client.clone();
         let status = Arc::clone(&shared_status);
+        let settings = Arc::clone(&shared_settings);
+        let history = Arc::clone(&shared_history);
 
         async move {
             Result::<_, hyper::Error>::Ok(service_fn(move |req| {
-                handle_status_request(req, Arc::clone(&status))
+                handle_request(
+                    req,
+                    client.clone(),
+                    Arc::clone(&status),
+                    Arc::clone(&settings),
+                    Arc::clone(&history),
+                )
             }))
         }
     });
     let server = Server::bind(&address)
         .serve(service)
-        .with_graceful_shutdown(handle_shutdown_signal(notify_close));
+        .with_graceful_shutdown({
+            let notify_close = Arc::clone(&notify_close);
+            async move { notify_close.notified().await }
+        });
+    task::spawn(handle_shutdown_signal(notify_close));
     join!(
         async {
             update_status_fut
@@ -129,134 +301,572 @@ async fn run_daemon(
         },
         async {
             server.await.expect("Failed to serve a service");
+        },
+        async {
+            if let Some(checkpoint_fut) = checkpoint_fut {
+                checkpoint_fut.await.expect("Failed to save checkpoints");
+            }
         }
     );
+    if serve.reconcile_on_shutdown {
+        let (since, live) = {
+            let status = final_status.read().expect("Failed to lock to read status");
+            (status.started_at(), status.live_counters())
+        };
+        info!("Reconciling the run with the blocks");
+        match reconcile(&final_client, run_id, Some(since), Some(live)).await {
+            Ok(report) => info!(
+                "Reconciliation report: {}",
+                serde_json::to_string_pretty(&report).expect("Failed to serialize the report")
+            ),
+            Err(err) => warn!("Failed to reconcile the run: {}", err),
+        }
+    }
     Ok(())
 }
 
+/// Counts pipeline events, reconnecting with an exponential backoff whenever the event
+/// stream is lost. If `reconcile_gaps` is set, outcomes of sent transactions committed
+/// while the stream was lost are recovered from the blocks produced during the gap.
 async fn update_status_according_to_events(
     client: Client,
     status: Arc<RwLock<Status>>,
+    reconcile_gaps: bool,
     notify_close: Arc<Notify>,
 ) {
-    let event_filter = FilterBox::Pipeline(PipelineEventFilter::new());
-    let mut event_stream = client.listen_for_events_async(event_filter).await.unwrap();
+    let closed = notify_close.notified();
+    tokio::pin!(closed);
+    let mut lost_at: Option<(Instant, DateTime<Utc>)> = None;
+    let mut backoff = MIN_RECONNECT_BACKOFF;
     loop {
-        let event = select! {
-            next = event_stream.next() => {
-                match next {
-                    Some(event) => event,
-                    None => break
+        let event_filter = FilterBox::Pipeline(PipelineEventFilter::new());
+        let connection = select! {
+            connection = client.listen_for_events(event_filter) => connection,
+            _ = &mut closed => return,
+        };
+        let mut event_stream = match connection {
+            Ok(event_stream) => event_stream,
+            Err(err) => {
+                warn!(
+                    "Failed to connect to the event stream, retrying in {:?}: {}",
+                    backoff, err
+                );
+                if lost_at.is_none() {
+                    lost_at = Some(event_stream_is_lost(&status, reconcile_gaps, false));
                 }
-            },
-            _ = notify_close.notified() => {
-                break;
+                select! {
+                    _ = time::sleep(backoff) => {},
+                    _ = &mut closed => return,
+                }
+                backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                continue;
             }
         };
-        debug!(event = ?event, "got an event");
-        if let Ok(Event::Pipeline(event)) = event {
-            match event.status() {
-                PipelineStatus::Validating => {}
-                PipelineStatus::Rejected(_) => {
-                    status
-                        .write()
-                        .expect("Failed to lock to write rejection timestamp to status")
-                        .tx_is_rejected();
+        backoff = MIN_RECONNECT_BACKOFF;
+        if let Some((lost_at, lost_since)) = lost_at.take() {
+            info!("Event stream is restored after {:?}", lost_at.elapsed());
+            status
+                .write()
+                .expect("Failed to lock to write event stream downtime")
+                .event_stream_is_restored(lost_at.elapsed());
+            if reconcile_gaps {
+                reconcile_gap(&client, &status, lost_since).await;
+                status
+                    .write()
+                    .expect("Failed to lock to resume expiry")
+                    .resume_expiry();
+            }
+        }
+        let mut is_closing = false;
+        loop {
+            let event = select! {
+                next = event_stream.next() => {
+                    match next {
+                        Some(event) => event,
+                        None => break
+                    }
+                },
+                _ = &mut closed => {
+                    is_closing = true;
+                    break;
                 }
-                PipelineStatus::Committed => {
+            };
+            debug!(event = ?event, "got an event");
+            match event {
+                Ok(Event::Pipeline(event)) => match (event.entity_kind(), event.status()) {
+                    (_, PipelineStatus::Validating) => {}
+                    (PipelineEntityKind::Block, PipelineStatus::Rejected(_)) => {
+                        status
+                            .write()
+                            .expect("Failed to lock to write block rejection to status")
+                            .block_is_rejected();
+                    }
+                    (PipelineEntityKind::Block, PipelineStatus::Committed) => {
+                        status
+                            .write()
+                            .expect("Failed to lock to write block commit to status")
+                            .block_is_committed();
+                    }
+                    (PipelineEntityKind::Transaction, PipelineStatus::Rejected(reason)) => {
+                        status
+                            .write()
+                            .expect("Failed to lock to write rejection timestamp to status")
+                            .tx_is_rejected(event.hash(), reason);
+                    }
+                    (PipelineEntityKind::Transaction, PipelineStatus::Committed) => {
+                        status
+                            .write()
+                            .expect("Failed to lock to write commit timestamp to status")
+                            .tx_is_committed(event.hash());
+                    }
+                },
+                Ok(_) => {
+                    warn!("TX with unknown status");
                     status
                         .write()
-                        .expect("Failed to lock to write commit timestamp to status")
-                        .tx_is_committed();
+                        .expect("Failed to lock to write unknown status")
+                        .tx_is_unknown()
+                }
+                Err(err) => {
+                    warn!("Event stream failed: {}", err);
+                    break;
                 }
             }
-        } else {
-            warn!("TX with unknown status");
-            status
+        }
+        event_stream.close().await;
+        if is_closing {
+            return;
+        }
+        warn!("Event stream is lost, reconnecting");
+        lost_at = Some(event_stream_is_lost(&status, reconcile_gaps, true));
+    }
+}
+
+/// Records the loss of the event stream and returns when it happened. If the gap is going
+/// to be reconciled, sent transactions are not timed out until then, as their outcomes
+/// can still be found in blocks.
+fn event_stream_is_lost(
+    status: &RwLock<Status>,
+    reconcile_gaps: bool,
+    is_disconnect: bool,
+) -> (Instant, DateTime<Utc>) {
+    let mut status = status
+        .write()
+        .expect("Failed to lock to write event stream loss");
+    if is_disconnect {
+        status.event_stream_is_lost();
+    }
+    if reconcile_gaps {
+        status.pause_expiry();
+    }
+    (Instant::now(), Utc::now())
+}
+
+/// Credits outcomes of sent transactions found in the blocks committed since `since`.
+async fn reconcile_gap(client: &Client, status: &RwLock<Status>, since: DateTime<Utc>) {
+    // Blocks are timestamped by peers, so some slack covers clock skew.
+    let since = since - chrono::Duration::seconds(CLOCK_SKEW_SECS);
+    match client.find_block_transactions_since(since).await {
+        Ok(transactions) => {
+            let mut status = status
                 .write()
-                .expect("Failed to lock to write unknown status")
-                .tx_is_unknown()
+                .expect("Failed to lock to write reconciled transactions");
+            for transaction in transactions {
+                status.tx_is_reconciled(&transaction.hash, transaction.rejection.as_ref());
+            }
+        }
+        Err(err) => warn!("Failed to reconcile the event stream gap: {}", err),
+    }
+}
+
+/// Periodically saves the status and the progress of operations to the state file,
+/// and once more on shutdown.
+async fn save_checkpoints(
+    status: Arc<RwLock<Status>>,
+    settings: Arc<RwLock<LoadSettings>>,
+    path: PathBuf,
+    period: Duration,
+    notify_close: Arc<Notify>,
+) {
+    let mut interval = time::interval(period);
+    let closed = notify_close.notified();
+    tokio::pin!(closed);
+    loop {
+        let is_closing = select! {
+            _ = interval.tick() => false,
+            _ = &mut closed => true,
+        };
+        // The locks are not held together, as the HTTP handler takes them in another order.
+        // Progress is read after the status, so it can only be ahead of it and entity names
+        // and sequence numbers of a resumed run are never reused.
+        let (status, in_flight) = {
+            let status = status.read().expect("Failed to lock to read status");
+            (status.clone(), status.in_flight())
+        };
+        let checkpoint = {
+            let settings = settings.read().expect("Failed to lock to read settings");
+            Checkpoint {
+                status,
+                operations: settings.operations().clone(),
+                next_index: settings.next_index(),
+                next_sequence: settings.next_sequence(),
+                in_flight,
+            }
+        };
+        let save = task::spawn_blocking({
+            let path = path.clone();
+            move || checkpoint.save(&path)
+        });
+        match save.await.expect("Failed to join the checkpoint save") {
+            Ok(()) => debug!("Checkpoint is saved"),
+            Err(err) => warn!("Failed to save a checkpoint: {:?}", err),
+        }
+        if is_closing {
+            return;
+        }
+    }
+}
+
+/// Periodically snapshots the status into the history.
+async fn record_history(
+    status: Arc<RwLock<Status>>,
+    settings: Arc<RwLock<LoadSettings>>,
+    history: Arc<RwLock<History>>,
+    period: Duration,
+    notify_close: Arc<Notify>,
+) {
+    let mut interval = time::interval(period);
+    let closed = notify_close.notified();
+    tokio::pin!(closed);
+    loop {
+        select! {
+            _ = interval.tick() => {},
+            _ = &mut closed => return,
+        }
+        let target_tps = settings
+            .read()
+            .expect("Failed to lock to read settings")
+            .target_tps();
+        let snapshot = status
+            .read()
+            .expect("Failed to lock to read status")
+            .snapshot(target_tps);
+        history
+            .write()
+            .expect("Failed to lock to write history")
+            .record(snapshot);
+    }
+}
+
+/// Periodically times out sent transactions which got no pipeline event within `timeout`.
+async fn expire_in_flight(
+    status: Arc<RwLock<Status>>,
+    timeout: Duration,
+    notify_close: Arc<Notify>,
+) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    let closed = notify_close.notified();
+    tokio::pin!(closed);
+    loop {
+        select! {
+            _ = interval.tick() => {},
+            _ = &mut closed => return,
         }
+        status
+            .write()
+            .expect("Failed to lock to expire transactions")
+            .expire_in_flight(timeout);
     }
-    event_stream.close().await;
 }
 
-fn perform_operations(
+/// Sends transactions on an open-loop schedule: the send times do not depend on how
+/// long previous submissions take. If `max_in_flight` submissions are already waiting
+/// for Torii, the next one waits for a free slot and its lateness is recorded.
+/// A change of the settings reschedules the next tick if the new ones call for an earlier one.
+async fn perform_operations(
     client: Client,
     status: Arc<RwLock<Status>>,
-    interval: Duration,
-    mut operations: HashMap<Operation, usize>,
-    is_closed: Arc<AtomicBool>,
+    settings: Arc<RwLock<LoadSettings>>,
+    run_id: RunId,
+    max_in_flight: usize,
+    notify_close: Arc<Notify>,
 ) {
     let alice_id = AccountId::from_str("alice@wonderland").expect("Failed to make Alice id");
     let wonderland_id =
         DomainId::new(Name::from_str("wonderland").expect("Failed to create Wodnerland name"));
-    while !operations.is_empty() {
-        if is_closed.load(Ordering::SeqCst) {
-            return;
+    let in_flight = Arc::new(Semaphore::new(max_in_flight));
+    let closed = notify_close.notified();
+    tokio::pin!(closed);
+    let mut submits_empty = false;
+    let changed = settings
+        .read()
+        .expect("Failed to lock to read settings")
+        .changes();
+    let mut scheduled_at = time::Instant::now();
+    loop {
+        select! {
+            _ = time::sleep_until(scheduled_at) => {},
+            _ = changed.notified() => {
+                let interval = settings
+                    .write()
+                    .expect("Failed to lock to read settings")
+                    .next_interval();
+                scheduled_at = scheduled_at.min(time::Instant::now() + interval);
+                continue;
+            },
+            _ = &mut closed => return,
         }
-        operations.retain(|op, count| {
-            let start_time = Instant::now();
-            debug!(operation = ?op, count = ?count, "perform operation");
-            let instructions =
-                make_instruction_by_operation(op, alice_id.clone(), wonderland_id.clone(), *count);
-            let res = client.submit_all(instructions);
-            let elapsed = Instant::now().duration_since(start_time);
-            if elapsed < interval {
-                thread::sleep(interval - elapsed);
+        let (next_action, interval) = {
+            let mut settings = settings.write().expect("Failed to lock to read settings");
+            let next_action = settings.next_action();
+            if let NextAction::Perform(op, _) = next_action {
+                settings.operation_is_scheduled(op);
+            }
+            (next_action, settings.next_interval())
+        };
+        let send_at = scheduled_at.into_std();
+        scheduled_at += interval;
+        let (operation, instructions) = match next_action {
+            NextAction::Pause => continue,
+            NextAction::Perform(op, index) => {
+                submits_empty = false;
+                debug!(operation = ?op, index = ?index, "perform operation");
+                (
+                    Some(op),
+                    make_instruction_by_operation(
+                        &op,
+                        alice_id.clone(),
+                        wonderland_id.clone(),
+                        &run_id,
+                        index,
+                    ),
+                )
+            }
+            NextAction::SubmitEmpty => {
+                if !submits_empty {
+                    info!("Submitting empty transactions");
+                    submits_empty = true;
+                }
+                (None, Vec::new())
             }
-            if let Err(err) = res {
+        };
+        let permit = select! {
+            permit = Arc::clone(&in_flight).acquire_owned() => {
+                permit.expect("Semaphore is never closed")
+            },
+            _ = &mut closed => return,
+        };
+        let sequence = settings
+            .write()
+            .expect("Failed to lock to take the sequence number")
+            .take_sequence();
+        let metadata = make_run_metadata(&run_id, operation, sequence);
+        let client = client.clone();
+        let status = Arc::clone(&status);
+        task::spawn(async move {
+            let _permit = permit;
+            status
+                .write()
+                .expect("Failed to lock to write status")
+                .send_is_late(send_at.elapsed());
+            if let Err(err) =
+                submit_instructions(&client, &status, operation, instructions, metadata).await
+            {
                 warn!("Submit failed: {}", err);
-                true
-            } else {
                 status
                     .write()
                     .expect("Failed to lock to write status")
-                    .tx_is_sent();
-                *count -= 1;
-                *count != 0
+                    .tx_submit_failed(operation);
             }
         });
     }
 }
 
-fn submit_empty_transactions(
+/// Submits the instructions with the metadata as a single transaction and registers it
+/// in the status, so that the commit latency can be measured once its pipeline event arrives.
+/// `operation` is `None` for empty transactions.
+async fn submit_instructions(
+    client: &Client,
+    status: &RwLock<Status>,
+    operation: Option<Operation>,
+    instructions: Vec<InstructionExpr>,
+    metadata: UnlimitedMetadata,
+) -> Result<()> {
+    let transaction = client.build_transaction(instructions, metadata)?;
+    let hash = transaction.hash().into();
+    let peer = client.choose_peer(&transaction);
+    // Registered before the submission, as its pipeline event may come before the response
+    status
+        .write()
+        .expect("Failed to lock to write status")
+        .tx_is_sent(hash, Instant::now(), peer.name(), operation);
+    if let Err(err) = client.submit_transaction_to(peer, transaction).await {
+        let mut status = status.write().expect("Failed to lock to write status");
+        status.tx_is_not_sent(&hash);
+        status.peer_submit_failed(peer.name());
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Status of the daemon together with its current load settings.
+#[derive(Serialize)]
+struct StatusDocument<'a> {
+    #[serde(flatten)]
+    status: &'a Status,
+    settings: &'a LoadSettings,
+}
+
+async fn handle_request(
+    req: Request<Body>,
     client: Client,
     status: Arc<RwLock<Status>>,
-    interval: Duration,
-    is_closed: Arc<AtomicBool>,
-) {
-    info!("Submitting empty transactions");
-    loop {
-        if is_closed.load(Ordering::SeqCst) {
-            return;
-        }
-        let start_time = Instant::now();
-        client
-            .submit_all(Vec::<InstructionExpr>::new().into_iter())
-            .expect("Failed to submit empty ISI");
-        status
-            .write()
-            .expect("Failed to lock to write status")
-            .tx_is_sent();
-        let elapsed = Instant::now().duration_since(start_time);
-        if elapsed < interval {
-            thread::sleep(interval - elapsed);
+    settings: Arc<RwLock<LoadSettings>>,
+    history: Arc<RwLock<History>>,
+) -> Result<Response<Body>, hyper::Error> {
+    let path = req.uri().path().to_owned();
+    if let Some(action) = path.strip_prefix("/control/") {
+        return handle_control_request(req.method().clone(), action, req, settings).await;
+    }
+    if path == "/metrics" {
+        let mut encoder = Encoder::default();
+        status.read().unwrap().encode_metrics(&mut encoder);
+        settings.read().unwrap().encode_metrics(&mut encoder);
+        client.encode_metrics(&mut encoder);
+        let res = Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(encoder.finish()))
+            .unwrap();
+        return Ok(res);
+    }
+    if path == "/history" {
+        return Ok(handle_history_request(req.uri().query(), &history));
+    }
+    if path == "/peers" {
+        return Ok(json_response(&client.peer_health()));
+    }
+    let status = status.read().unwrap();
+    let settings = settings.read().unwrap();
+    Ok(json_response(&StatusDocument {
+        status: &status,
+        settings: &settings,
+    }))
+}
+
+/// Serves the snapshots taken within the optional `from` and `to` RFC 3339 bounds
+/// as JSON or, with `format=csv`, as CSV. Parameters are percent-decoded, so a `+` of
+/// a time offset has to be given as `%2B`.
+fn handle_history_request(query: Option<&str>, history: &RwLock<History>) -> Response<Body> {
+    let mut from = None;
+    let mut to = None;
+    let mut is_csv = false;
+    for (key, value) in form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        match key.as_ref() {
+            "from" | "to" => {
+                let bound = match DateTime::parse_from_rfc3339(&value) {
+                    Ok(bound) => bound.with_timezone(&Utc),
+                    Err(err) => {
+                        return error_response(
+                            StatusCode::BAD_REQUEST,
+                            format!("Invalid `{}` time: {}", key, err),
+                        )
+                    }
+                };
+                if key == "from" {
+                    from = Some(bound);
+                } else {
+                    to = Some(bound);
+                }
+            }
+            "format" => is_csv = value == "csv",
+            _ => {}
         }
     }
+    let history = history.read().unwrap();
+    let snapshots = history.range(from, to);
+    if is_csv {
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/csv")
+            .body(Body::from(history::to_csv(snapshots)))
+            .unwrap()
+    } else {
+        json_response(&snapshots.collect::<Vec<_>>())
+    }
 }
 
-async fn handle_status_request(
-    _req: Request<Body>,
-    status: Arc<RwLock<Status>>,
+async fn handle_control_request(
+    method: Method,
+    action: &str,
+    req: Request<Body>,
+    settings: Arc<RwLock<LoadSettings>>,
 ) -> Result<Response<Body>, hyper::Error> {
-    let guard = status.read().unwrap();
-    let str_status = serde_json::to_string(&*guard).unwrap();
-    let res = Response::builder()
+    let body = hyper::body::to_bytes(req.into_body()).await?;
+    let mut settings = settings.write().unwrap();
+    match (method, action) {
+        (Method::POST, "pause") => {
+            info!("pausing the load");
+            settings.pause();
+        }
+        (Method::POST, "resume") => {
+            info!("resuming the load");
+            settings.resume();
+        }
+        (Method::PUT, "tps") => match serde_json::from_slice::<PositiveFloat>(&body) {
+            Ok(tps) => {
+                info!(tps = ?tps, "changing TPS");
+                settings.set_shape(tps.into());
+            }
+            Err(err) => return Ok(error_response(StatusCode::BAD_REQUEST, err)),
+        },
+        (Method::PUT, "shape") => match serde_json::from_slice::<LoadShape>(&body) {
+            Ok(shape) => {
+                if let Err(err) = shape.validate() {
+                    return Ok(error_response(StatusCode::BAD_REQUEST, err));
+                }
+                info!(shape = ?shape, "changing load shape");
+                settings.set_shape(shape);
+            }
+            Err(err) => return Ok(error_response(StatusCode::BAD_REQUEST, err)),
+        },
+        (Method::PUT, "arrival") => match serde_json::from_slice::<Arrival>(&body) {
+            Ok(arrival) => {
+                info!(arrival = ?arrival, "changing arrival process");
+                settings.set_arrival(arrival);
+            }
+            Err(err) => return Ok(error_response(StatusCode::BAD_REQUEST, err)),
+        },
+        (Method::POST, "operations") => {
+            match serde_json::from_slice::<BTreeMap<Operation, OperationSettings>>(&body) {
+                Ok(operations) => {
+                    info!(operations = ?operations, "changing operations");
+                    settings.set_operations(operations);
+                }
+                Err(err) => return Ok(error_response(StatusCode::BAD_REQUEST, err)),
+            }
+        }
+        (_, "pause" | "resume" | "tps" | "shape" | "arrival" | "operations") => {
+            return Ok(error_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method is not allowed",
+            ))
+        }
+        _ => return Ok(error_response(StatusCode::NOT_FOUND, "Unknown control")),
+    }
+    Ok(json_response(&*settings))
+}
+
+fn json_response(value: &impl Serialize) -> Response<Body> {
+    Response::builder()
         .header(header::CONTENT_TYPE, "application/json")
-        .body(Body::from(str_status))
-        .unwrap();
-    Ok(res)
+        .body(Body::from(serde_json::to_string(value).unwrap()))
+        .unwrap()
+}
+
+fn error_response(code: StatusCode, err: impl Display) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .body(Body::from(err.to_string()))
+        .unwrap()
 }
 
 async fn handle_shutdown_signal(notify_close: Arc<Notify>) {
@@ -1,22 +1,86 @@
+pub mod cleanup;
 pub mod daemon;
 pub mod oneshot;
+pub mod reconcile;
+pub mod run;
 
-use crate::{operation::Operation, value::ValueWrapper};
+use crate::{
+    async_client::{Client, SubmitStrategy},
+    operation::Operation,
+    run_id::{RunId, METADATA_OPERATION, METADATA_RUN_ID, METADATA_SENT_AT_MS, METADATA_SEQUENCE},
+    value::ValueWrapper,
+};
+use chrono::Utc;
 use iroha_crypto::prelude::*;
 use iroha_data_model::prelude::*;
 use iroha_primitives::fixed::Fixed;
 use rand::prelude::*;
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc, time::Duration};
+use structopt::StructOpt;
+use tokio::{select, sync::Notify, time};
 
+/// Interval between health probes of peers out of rotation.
+const PEER_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Arguments shared by commands submitting operations.
+#[derive(Debug, StructOpt)]
+pub struct SubmitArgs {
+    /// Time to wait for the outcome of a transaction before counting it as timed out,
+    /// `TRANSACTION_STATUS_TIMEOUT_MS` of the client config by default
+    #[structopt(long)]
+    status_timeout_ms: Option<u64>,
+    /// How to choose the peer for every transaction: `round-robin`, `random`, `weighted`
+    /// or `sticky` to the operation
+    #[structopt(long, default_value = "round-robin")]
+    submit_strategy: SubmitStrategy,
+    /// Identifier ending the names of all registered entities, random if absent
+    #[structopt(long, env = "RUN_ID")]
+    run_id: Option<RunId>,
+}
+
+/// Makes metadata attributing a transaction to the load generator: the run, the operation
+/// (`Empty` for empty transactions), the sequence number of the transaction within the
+/// run and the time it is sent, so latency can be reconstructed from block contents.
+fn make_run_metadata(
+    run_id: &RunId,
+    operation: Option<Operation>,
+    sequence: u64,
+) -> UnlimitedMetadata {
+    let operation = operation.map_or_else(|| "Empty".to_owned(), |op| format!("{:?}", op));
+    let sent_at_ms = u64::try_from(Utc::now().timestamp_millis()).unwrap_or_default();
+    let mut metadata = UnlimitedMetadata::new();
+    for (key, value) in [
+        (METADATA_RUN_ID, Value::String(run_id.to_string())),
+        (METADATA_OPERATION, Value::String(operation)),
+        (
+            METADATA_SEQUENCE,
+            Value::Numeric(NumericValue::U64(sequence)),
+        ),
+        (
+            METADATA_SENT_AT_MS,
+            Value::Numeric(NumericValue::U64(sent_at_ms)),
+        ),
+    ] {
+        metadata.insert(
+            Name::from_str(key).expect("Failed to create a metadata key"),
+            value,
+        );
+    }
+    metadata
+}
+
+/// Makes instructions of the operation registering entities whose names are made unique
+/// by the index and the run id.
 fn make_instruction_by_operation(
     op: &Operation,
     test_account_id: AccountId,
     test_domain_id: DomainId,
+    run_id: &RunId,
     index: usize,
 ) -> Vec<InstructionExpr> {
     match op {
         Operation::RegisterAccount => {
-            let new_account_name = Name::from_str(format!("alice{}", index).as_str())
+            let new_account_name = Name::from_str(&run_id.name(format!("alice{}", index)))
                 .expect("Failed to create a new account name");
             let new_account_id: AccountId = AccountId::new(new_account_name, test_domain_id);
             let (public_key, _) = KeyPair::generate()
@@ -25,13 +89,13 @@ fn make_instruction_by_operation(
             vec![RegisterExpr::new(Account::new(new_account_id, [public_key])).into()]
         }
         Operation::RegisterDomain => {
-            let new_domain_name = Name::from_str(format!("wonderland{}", index).as_str())
+            let new_domain_name = Name::from_str(&run_id.name(format!("wonderland{}", index)))
                 .expect("Failed to create a new domain name");
             let new_domain_id: DomainId = DomainId::new(new_domain_name);
             vec![RegisterExpr::new(Domain::new(new_domain_id)).into()]
         }
         Operation::RegisterAssetQuantity => {
-            let new_asset_name = Name::from_str(format!("rose_quantity{}", index).as_str())
+            let new_asset_name = Name::from_str(&run_id.name(format!("rose_quantity{}", index)))
                 .expect("Failed to create a new asset name");
             let new_asset_definition_id: AssetDefinitionId =
                 AssetDefinitionId::new(new_asset_name, test_domain_id);
@@ -50,8 +114,9 @@ fn make_instruction_by_operation(
             ]
         }
         Operation::RegisterAssetBigQuantity => {
-            let new_asset_name = Name::from_str(format!("rose_big_quantity{}", index).as_str())
-                .expect("Failed to create a new asset name");
+            let new_asset_name =
+                Name::from_str(&run_id.name(format!("rose_big_quantity{}", index)))
+                    .expect("Failed to create a new asset name");
             let new_asset_definition_id: AssetDefinitionId =
                 AssetDefinitionId::new(new_asset_name, test_domain_id);
             let mut new_asset_definition =
@@ -69,7 +134,7 @@ fn make_instruction_by_operation(
             ]
         }
         Operation::RegisterAssetFixed => {
-            let new_asset_name = Name::from_str(format!("rose_fixed{}", index).as_str())
+            let new_asset_name = Name::from_str(&run_id.name(format!("rose_fixed{}", index)))
                 .expect("Failed to create a new asset name");
             let new_asset_definition_id: AssetDefinitionId =
                 AssetDefinitionId::new(new_asset_name, test_domain_id);
@@ -87,7 +152,7 @@ fn make_instruction_by_operation(
             ]
         }
         Operation::RegisterAssetStore => {
-            let new_asset_name = Name::from_str(format!("rose_store{}", index).as_str())
+            let new_asset_name = Name::from_str(&run_id.name(format!("rose_store{}", index)))
                 .expect("Failed to create a new asset name");
             let new_asset_definition_id: AssetDefinitionId =
                 AssetDefinitionId::new(new_asset_name, test_domain_id);
@@ -115,7 +180,7 @@ fn make_instruction_by_operation(
         }
         Operation::TransferAsset => {
             // Make a new sender asset
-            let new_asset_name = Name::from_str(format!("rose{}_to_transfer", index).as_str())
+            let new_asset_name = Name::from_str(&run_id.name(format!("rose{}_to_transfer", index)))
                 .expect("Failed to create a new asset name");
             let new_asset_definition_id =
                 AssetDefinitionId::new(new_asset_name, test_domain_id.clone());
@@ -127,7 +192,7 @@ fn make_instruction_by_operation(
 
             // Make a new recipient account
             let new_recipient_account_name =
-                Name::from_str(format!("bob{}_to_transfer", index).as_str())
+                Name::from_str(&run_id.name(format!("bob{}_to_transfer", index)))
                     .expect("Failed to create a new account name");
             let new_recipient_account_id =
                 AccountId::new(new_recipient_account_name, test_domain_id);
@@ -158,7 +223,7 @@ fn make_instruction_by_operation(
         }
         Operation::MintAsset => {
             // Make a new asset
-            let new_asset_name = Name::from_str(format!("rose{}_to_mint", index).as_str())
+            let new_asset_name = Name::from_str(&run_id.name(format!("rose{}_to_mint", index)))
                 .expect("Failed to create a new asset name");
             let new_asset_definition_id =
                 AssetDefinitionId::new(new_asset_name, test_domain_id.clone());
@@ -167,7 +232,7 @@ fn make_instruction_by_operation(
             let new_asset = Asset::new(new_asset_id.clone(), AssetValue::Quantity(0));
 
             // Make a new account
-            let new_account_name = Name::from_str(format!("bob{}_to_mint", index).as_str())
+            let new_account_name = Name::from_str(&run_id.name(format!("bob{}_to_mint", index)))
                 .expect("Failed to create a new account name");
             let new_account_id = AccountId::new(new_account_name, test_domain_id);
             let (public_key, _) = KeyPair::generate()
@@ -184,3 +249,17 @@ fn make_instruction_by_operation(
         }
     }
 }
+
+/// Periodically probes peers taken out of rotation to bring them back once they respond.
+async fn probe_unhealthy_peers(client: Client, notify_close: Arc<Notify>) {
+    let mut interval = time::interval(PEER_PROBE_INTERVAL);
+    let closed = notify_close.notified();
+    tokio::pin!(closed);
+    loop {
+        select! {
+            _ = interval.tick() => {},
+            _ = &mut closed => return,
+        }
+        client.probe_unhealthy_peers().await;
+    }
+}
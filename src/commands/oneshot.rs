@@ -1,48 +1,70 @@
-use super::make_instruction_by_operation;
+use super::{make_instruction_by_operation, make_run_metadata, probe_unhealthy_peers, SubmitArgs};
 use crate::{
     args::RunArgs,
-    async_client::{Client as AsyncClient, SubmitBlockingStatus},
+    async_client::{Client as AsyncClient, SubmitBlockingStatus, SubmitStrategy},
+    config::{load_configuration, status_timeout},
     operation::Operation,
+    run_id::RunId,
     status::Status,
 };
 use async_trait::async_trait;
 use color_eyre::eyre::{Result, WrapErr as _};
-use iroha_client::client::Client;
-use iroha_config::client::Configuration;
 use iroha_data_model::prelude::*;
 use std::{
-    fs::File,
     io::Write,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{Arc, RwLock},
+    time::Instant,
 };
 use structopt::StructOpt;
 use tokio::task;
+use tracing::warn;
 
 #[derive(Debug, StructOpt)]
 pub struct Args {
+    #[structopt(long, default_value = "config.json")]
+    config: PathBuf,
     #[structopt(long, default_value = "100")]
     count: usize,
     #[structopt(long, required = true)]
     operation: Operation,
+    #[structopt(flatten)]
+    submit: SubmitArgs,
 }
 
 #[async_trait]
 impl RunArgs for Args {
     async fn run<T: Write + Send>(self, writer: &mut std::io::BufWriter<T>) -> Result<()> {
-        let status = run_oneshot_operation(self.count, self.operation).await?;
+        let status = run_oneshot_operation(
+            &self.config,
+            self.count,
+            self.operation,
+            self.submit.status_timeout_ms,
+            self.submit.submit_strategy,
+            self.submit.run_id.unwrap_or_else(RunId::generate),
+        )
+        .await?;
         writeln!(writer, "{}", serde_json::to_string_pretty(&status)?)
             .wrap_err("Failed to pretty print a result")?;
         Ok(())
     }
 }
 
-async fn run_oneshot_operation(count: usize, operation: Operation) -> Result<Status> {
-    let config_file = File::open("config.json").expect("`config.json` not found.");
-    let cfg: Configuration =
-        serde_json::from_reader(config_file).expect("Failed to deserialize configuration.");
-    let client: AsyncClient = AsyncClient::from(Client::new(&cfg)?);
-    let status = Arc::new(RwLock::new(Status::default()));
+async fn run_oneshot_operation(
+    config_path: &Path,
+    count: usize,
+    operation: Operation,
+    status_timeout_ms: Option<u64>,
+    submit_strategy: SubmitStrategy,
+    run_id: RunId,
+) -> Result<Status> {
+    let (cfg, peers) = load_configuration(config_path)?;
+    let client = AsyncClient::new(&cfg, &peers, submit_strategy)?
+        .with_status_timeout(status_timeout(&cfg, status_timeout_ms));
+    let status = Arc::new(RwLock::new(Status::for_run(run_id.clone())));
+    // Peers out of rotation are brought back while the operations are in progress
+    let probe_handle = task::spawn(probe_unhealthy_peers(client.clone(), Arc::default()));
     let mut operation_handles = vec![];
     let alice_id = AccountId::from_str("alice@wonderland").expect("Failed to make Alice id");
     let wonderland_id =
@@ -51,27 +73,47 @@ async fn run_oneshot_operation(count: usize, operation: Operation) -> Result<Sta
         let status = Arc::clone(&status);
         let alice_id = alice_id.clone();
         let wonderland_id = wonderland_id.clone();
+        let run_id = run_id.clone();
         let client = client.clone();
         let handle = task::spawn(async move {
-            status
-                .write()
-                .expect("Failed to lock to update status")
-                .tx_is_sent();
             let instructions =
-                make_instruction_by_operation(&operation, alice_id, wonderland_id, index);
+                make_instruction_by_operation(&operation, alice_id, wonderland_id, &run_id, index);
+            let metadata = make_run_metadata(&run_id, Some(operation), index as u64);
+            let transaction = client
+                .build_transaction(instructions, metadata)
+                .expect("Failed to build the transaction");
+            let hash = transaction.hash().into();
+            let peer = client.choose_peer(&transaction);
             let res = client
-                .submit_all_blocking(instructions)
-                .await
-                .expect("Failed to submit the transaction");
+                .submit_transaction_blocking_with(peer, transaction, || {
+                    status
+                        .write()
+                        .expect("Failed to lock to update status")
+                        .tx_is_sent(hash, Instant::now(), peer.name(), Some(operation));
+                })
+                .await;
             let mut guard = status.write().expect("Failed to lock to update status");
+            let res = match res {
+                Ok(res) => res,
+                Err(err) => {
+                    warn!("Failed to submit the transaction: {:?}", err);
+                    // The peer is not to blame if the event subscription failed before the submit
+                    if guard.tx_is_not_sent(&hash) {
+                        guard.peer_submit_failed(peer.name());
+                    }
+                    guard.tx_submit_failed(Some(operation));
+                    return;
+                }
+            };
             match res {
                 SubmitBlockingStatus::Committed(_) => {
-                    guard.tx_is_committed();
+                    guard.tx_is_committed(&hash);
                 }
-                SubmitBlockingStatus::Rejected(_) => {
-                    guard.tx_is_rejected();
+                SubmitBlockingStatus::Rejected(reason) => {
+                    guard.tx_is_rejected(&hash, &reason);
                 }
                 SubmitBlockingStatus::Unknown => guard.tx_is_unknown(),
+                SubmitBlockingStatus::TimedOut => guard.tx_is_timed_out(&hash),
             };
         });
 
@@ -81,6 +123,7 @@ async fn run_oneshot_operation(count: usize, operation: Operation) -> Result<Sta
     for handle in operation_handles {
         handle.await.expect("Failed to handle a spawned task");
     }
+    probe_handle.abort();
 
     let status = status
         .read()
@@ -0,0 +1,67 @@
+use crate::{
+    args::RunArgs,
+    async_client::{Client, SubmitStrategy},
+    checkpoint::Checkpoint,
+    config::load_configuration,
+    reconcile::{reconcile, ReconcileReport},
+    run_id::RunId,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{bail, eyre, Result, WrapErr as _};
+use std::{io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    #[structopt(long, default_value = "config.json")]
+    config: PathBuf,
+    /// State file of the daemon whose run is compared with the blocks
+    #[structopt(long)]
+    state_file: Option<PathBuf>,
+    /// Run whose transactions are looked for, taken from the state file if absent
+    #[structopt(long, required_unless = "state_file")]
+    run_id: Option<RunId>,
+    /// Time of the oldest scanned block in RFC 3339, the start of the run in the state
+    /// file or the first block by default
+    #[structopt(long)]
+    since: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+impl RunArgs for Args {
+    async fn run<T: Write + Send>(self, writer: &mut std::io::BufWriter<T>) -> Result<()> {
+        let report = reconcile_run(self).await?;
+        writeln!(writer, "{}", serde_json::to_string_pretty(&report)?)
+            .wrap_err("Failed to pretty print a result")?;
+        Ok(())
+    }
+}
+
+async fn reconcile_run(args: Args) -> Result<ReconcileReport> {
+    let (cfg, peers) = load_configuration(&args.config)?;
+    let client = Client::new(&cfg, &peers, SubmitStrategy::default())?;
+    match &args.state_file {
+        Some(path) => {
+            let checkpoint = Checkpoint::load(path)?;
+            // Transactions in flight are kept apart from the status in the state file
+            let mut status = checkpoint.status;
+            status.in_flight_is_lost(checkpoint.in_flight);
+            let run_id = status
+                .run_id()
+                .cloned()
+                .ok_or_else(|| eyre!("The state file has no run id"))?;
+            if matches!(&args.run_id, Some(given) if *given != run_id) {
+                bail!("The state file `{}` is of run {}", path.display(), run_id);
+            }
+            let since = args.since.unwrap_or_else(|| status.started_at());
+            reconcile(&client, run_id, Some(since), Some(status.live_counters())).await
+        }
+        None => {
+            let run_id = args
+                .run_id
+                .ok_or_else(|| eyre!("Either a run id or a state file is required"))?;
+            reconcile(&client, run_id, args.since, None).await
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use super::daemon::{run_daemon, ServeArgs};
+use crate::{args::RunArgs, control::LoadSettings, number::PositiveFloat, scenario::Scenario};
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    #[structopt(flatten)]
+    serve: ServeArgs,
+    /// Path to the JSON file describing the scenario
+    #[structopt(short = "s", long)]
+    scenario: PathBuf,
+}
+
+#[async_trait]
+impl RunArgs for Args {
+    async fn run<T: Write + Send>(self, _writer: &mut std::io::BufWriter<T>) -> Result<()> {
+        let scenario = Scenario::from_path(&self.scenario)?;
+        let mut settings = LoadSettings::new(
+            PositiveFloat::ONE.into(),
+            scenario.arrival,
+            scenario.seed,
+            BTreeMap::new(),
+        );
+        // Nothing is sent until the scenario starts its first stage
+        settings.pause();
+        run_daemon(self.serve, settings, Some(scenario)).await
+    }
+}
@@ -0,0 +1,164 @@
+use crate::number::{default_weight, PositiveFloat};
+use color_eyre::eyre::{eyre, Result, WrapErr as _};
+use iroha_config::client::Configuration;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{env, fs::File, path::Path, time::Duration};
+use tracing::info;
+use url::Url;
+
+/// Field of the configuration listing peers to submit transactions to,
+/// read by the script itself rather than the client.
+const PEERS_FIELD: &str = "PEERS";
+
+/// Configuration fields which can be overridden by the environment variable of the same name.
+/// Values are parsed as JSON and fall back to plain strings, so both `TORII_API_URL=http://...`
+/// and `PRIVATE_KEY='{"digest_function": ...}'` are accepted.
+const OVERRIDABLE_FIELDS: [&str; 10] = [
+    "PUBLIC_KEY",
+    "PRIVATE_KEY",
+    "ACCOUNT_ID",
+    "BASIC_AUTH",
+    "TORII_API_URL",
+    "TORII_TELEMETRY_URL",
+    "TRANSACTION_TIME_TO_LIVE_MS",
+    "TRANSACTION_STATUS_TIMEOUT_MS",
+    "ADD_TRANSACTION_NONCE",
+    PEERS_FIELD,
+];
+
+/// Torii endpoint of a peer given in the `PEERS` list either as a URL or as
+/// `{"url": ..., "weight": ...}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "PeerEndpointSpec")]
+pub struct PeerEndpoint {
+    pub url: Url,
+    /// Share of transactions for the weighted submission strategy.
+    pub weight: PositiveFloat,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PeerEndpointSpec {
+    Url(Url),
+    Weighted {
+        url: Url,
+        #[serde(default = "default_weight")]
+        weight: PositiveFloat,
+    },
+}
+
+impl From<PeerEndpointSpec> for PeerEndpoint {
+    fn from(spec: PeerEndpointSpec) -> Self {
+        match spec {
+            PeerEndpointSpec::Url(url) => Self {
+                url,
+                weight: default_weight(),
+            },
+            PeerEndpointSpec::Weighted { url, weight } => Self { url, weight },
+        }
+    }
+}
+
+/// Reads the client configuration from the file and applies environment overrides.
+/// Returns it together with the peers to submit transactions to, which are only
+/// `TORII_API_URL` unless the `PEERS` list is given.
+///
+/// # Errors
+/// if the file can't be read or the resulting configuration is invalid.
+pub fn load_configuration(path: &Path) -> Result<(Configuration, Vec<PeerEndpoint>)> {
+    let config_file = File::open(path)
+        .wrap_err_with(|| format!("Failed to open the config file `{}`", path.display()))?;
+    let mut config: Value = serde_json::from_reader(config_file)
+        .wrap_err_with(|| format!("Failed to parse the config file `{}`", path.display()))?;
+    let fields = config
+        .as_object_mut()
+        .ok_or_else(|| eyre!("The configuration has to be a JSON object"))?;
+    override_fields(fields, |field| env::var(field).ok());
+    let peers = fields
+        .remove(PEERS_FIELD)
+        .map(serde_json::from_value::<Vec<PeerEndpoint>>)
+        .transpose()
+        .wrap_err("Failed to deserialize the list of peers")?;
+    let config: Configuration =
+        serde_json::from_value(config).wrap_err("Failed to deserialize configuration")?;
+    let peers = match peers {
+        Some(peers) if !peers.is_empty() => peers,
+        _ => vec![PeerEndpoint {
+            url: config.torii_api_url.clone(),
+            weight: default_weight(),
+        }],
+    };
+    Ok((config, peers))
+}
+
+/// Replaces overridable fields with the values given by `var`, parsed as JSON
+/// or taken as plain strings.
+fn override_fields(fields: &mut Map<String, Value>, var: impl Fn(&str) -> Option<String>) {
+    for field in OVERRIDABLE_FIELDS {
+        if let Some(raw) = var(field) {
+            info!(field, "Overriding configuration field from the environment");
+            let value = serde_json::from_str(&raw).unwrap_or(Value::String(raw));
+            fields.insert(field.to_owned(), value);
+        }
+    }
+}
+
+/// Returns the time after which a sent transaction without an outcome counts as timed out:
+/// `timeout_ms` if given, otherwise `TRANSACTION_STATUS_TIMEOUT_MS` of the configuration.
+pub fn status_timeout(config: &Configuration, timeout_ms: Option<u64>) -> Duration {
+    Duration::from_millis(timeout_ms.unwrap_or(config.transaction_status_timeout_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn overrides_are_parsed_as_json_or_taken_as_strings() {
+        let mut fields =
+            json!({"TORII_API_URL": "http://127.0.0.1:8080", "ACCOUNT_ID": "alice@wonderland"});
+        let fields = fields.as_object_mut().expect("Object");
+        override_fields(fields, |field| match field {
+            "TORII_API_URL" => Some("http://10.0.0.2:8080".to_owned()),
+            "TRANSACTION_STATUS_TIMEOUT_MS" => Some("5000".to_owned()),
+            "PRIVATE_KEY" => Some(r#"{"digest_function": "ed25519"}"#.to_owned()),
+            "UNKNOWN" => Some("ignored".to_owned()),
+            _ => None,
+        });
+        assert_eq!(fields["TORII_API_URL"], json!("http://10.0.0.2:8080"));
+        assert_eq!(fields["TRANSACTION_STATUS_TIMEOUT_MS"], json!(5000));
+        assert_eq!(fields["PRIVATE_KEY"], json!({"digest_function": "ed25519"}));
+        assert_eq!(fields["ACCOUNT_ID"], json!("alice@wonderland"));
+        assert!(!fields.contains_key("UNKNOWN"));
+    }
+
+    #[test]
+    fn peers_are_urls_or_weighted_objects() {
+        let peers: Vec<PeerEndpoint> = serde_json::from_value(json!([
+            "http://127.0.0.1:8080",
+            {"url": "http://127.0.0.1:8081", "weight": 2},
+            {"url": "http://127.0.0.1:8082"},
+        ]))
+        .expect("Valid peers");
+        let peers = peers
+            .iter()
+            .map(|peer| (peer.url.as_str(), f64::from(peer.weight)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            peers,
+            [
+                ("http://127.0.0.1:8080/", 1.0),
+                ("http://127.0.0.1:8081/", 2.0),
+                ("http://127.0.0.1:8082/", 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn peers_need_a_positive_weight() {
+        let peers = json!([{"url": "http://127.0.0.1:8081", "weight": 0}]);
+        assert!(serde_json::from_value::<Vec<PeerEndpoint>>(peers).is_err());
+    }
+}
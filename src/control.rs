@@ -0,0 +1,334 @@
+use crate::{
+    arrival::Arrival,
+    load_shape::LoadShape,
+    metrics::{Encoder, MetricKind},
+    number::{default_weight, PositiveFloat},
+    operation::Operation,
+};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    random,
+    rngs::StdRng,
+    SeedableRng,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+/// Load settings of the daemon which can be changed while it is running.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadSettings {
+    paused: bool,
+    shape: LoadShape,
+    /// Rate the shape gave on the latest tick.
+    target_tps: f64,
+    #[serde(skip)]
+    shape_applied_at: Instant,
+    arrival: Arrival,
+    /// Seed of the generator driving intervals and the choice of operations.
+    seed: u64,
+    #[serde(skip)]
+    rng: StdRng,
+    /// Transactions left in the current burst of the bursty arrival.
+    #[serde(skip)]
+    burst_left: usize,
+    /// Name of the scenario stage being performed.
+    stage: Option<String>,
+    /// Set while the rate is the sum of the rates of operations given as their weights,
+    /// so it drops as operations run out of their counts.
+    #[serde(skip)]
+    rate_from_operations: bool,
+    operations: BTreeMap<Operation, OperationSettings>,
+    /// Index used to name entities of the next operation.
+    next_index: usize,
+    /// Sequence number of the next sent transaction.
+    next_sequence: u64,
+    /// Notified whenever the settings are changed from outside of the scheduler.
+    #[serde(skip)]
+    changed: Arc<Notify>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperationSettings {
+    /// Share of the operation in the load relative to other operations.
+    #[serde(default = "default_weight")]
+    pub weight: PositiveFloat,
+    /// Amount of transactions left to send, unlimited if absent.
+    #[serde(default)]
+    pub count: Option<usize>,
+}
+
+/// What the daemon should do on its next tick.
+#[derive(Debug, Clone, Copy)]
+pub enum NextAction {
+    Pause,
+    Perform(Operation, usize),
+    SubmitEmpty,
+}
+
+impl LoadSettings {
+    /// Makes settings with a random seed if none is given.
+    pub fn new(
+        shape: LoadShape,
+        arrival: Arrival,
+        seed: Option<u64>,
+        operations: BTreeMap<Operation, OperationSettings>,
+    ) -> Self {
+        let seed = seed.unwrap_or_else(random);
+        let mut settings = Self {
+            paused: false,
+            shape,
+            target_tps: shape.tps_at(Duration::ZERO),
+            shape_applied_at: Instant::now(),
+            arrival,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            burst_left: 0,
+            stage: None,
+            rate_from_operations: false,
+            operations: BTreeMap::new(),
+            next_index: 0,
+            next_sequence: 0,
+            changed: Arc::default(),
+        };
+        settings.set_arrival(arrival);
+        settings.set_operations(operations);
+        settings
+    }
+
+    /// Returns the notification of changes, so the scheduler can reconsider a tick
+    /// scheduled according to the old settings.
+    pub fn changes(&self) -> Arc<Notify> {
+        Arc::clone(&self.changed)
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.changed.notify_one();
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.changed.notify_one();
+    }
+
+    pub fn set_shape(&mut self, shape: LoadShape) {
+        self.rate_from_operations = false;
+        self.shape = shape;
+        self.shape_applied_at = Instant::now();
+        self.target_tps = shape.tps_at(Duration::ZERO);
+        self.changed.notify_one();
+    }
+
+    pub fn set_arrival(&mut self, arrival: Arrival) {
+        self.arrival = arrival;
+        self.burst_left = match arrival {
+            Arrival::Bursty { burst_size } => burst_size.get() - 1,
+            Arrival::Fixed | Arrival::Poisson => 0,
+        };
+        self.changed.notify_one();
+    }
+
+    /// Replaces operations left to perform. Entity names keep counting from the
+    /// current index, so the new operations do not collide with already sent ones.
+    pub fn set_operations(&mut self, operations: BTreeMap<Operation, OperationSettings>) {
+        self.operations = operations
+            .into_iter()
+            .filter(|(_, settings)| settings.count != Some(0))
+            .collect();
+        self.changed.notify_one();
+    }
+
+    pub fn operations(&self) -> &BTreeMap<Operation, OperationSettings> {
+        &self.operations
+    }
+
+    pub fn next_index(&self) -> usize {
+        self.next_index
+    }
+
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Returns the sequence number for a transaction about to be sent and advances it.
+    pub fn take_sequence(&mut self) -> u64 {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        sequence
+    }
+
+    /// Continues a run from a checkpoint: restores operations left to perform, the index
+    /// of the next operation and the sequence number of the next transaction, so entity
+    /// names and sequence numbers of the previous run are not reused.
+    pub fn restore_progress(
+        &mut self,
+        operations: BTreeMap<Operation, OperationSettings>,
+        next_index: usize,
+        next_sequence: u64,
+    ) {
+        self.set_operations(operations);
+        self.next_index = next_index;
+        self.next_sequence = next_sequence;
+    }
+
+    /// Switches the load to the given scenario stage. Without a shape the rate is the sum
+    /// of the weights of operations, which are their rates then.
+    pub fn start_stage(
+        &mut self,
+        name: String,
+        shape: Option<LoadShape>,
+        operations: BTreeMap<Operation, OperationSettings>,
+    ) {
+        self.stage = Some(name);
+        self.set_operations(operations);
+        match shape {
+            Some(shape) => self.set_shape(shape),
+            None => {
+                self.set_shape(self.operations_tps().into());
+                self.rate_from_operations = true;
+            }
+        }
+    }
+
+    /// Sum of the weights of operations left to perform, at least one.
+    fn operations_tps(&self) -> PositiveFloat {
+        let tps = self
+            .operations
+            .values()
+            .map(|settings| f64::from(settings.weight))
+            .sum();
+        PositiveFloat::new(tps).unwrap_or(PositiveFloat::ONE)
+    }
+
+    /// Leaves the last scenario stage, so only empty transactions are sent further.
+    pub fn finish_stages(&mut self) {
+        self.stage = None;
+        self.set_operations(BTreeMap::new());
+    }
+
+    /// Target rate as of the latest scheduled transaction.
+    pub fn target_tps(&self) -> f64 {
+        self.target_tps
+    }
+
+    /// Returns `true` if all operations have run out of their counts.
+    pub fn is_exhausted(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Updates the target rate according to the shape and samples the interval to the
+    /// next tick from the arrival process.
+    pub fn next_interval(&mut self) -> Duration {
+        self.target_tps = self.shape.tps_at(self.shape_applied_at.elapsed());
+        let gap = self
+            .arrival
+            .next_gap(self.target_tps, &mut self.rng, &mut self.burst_left);
+        Duration::from_secs_f64(gap)
+    }
+
+    /// Samples the next operation randomly according to the weights of operations.
+    pub fn next_action(&mut self) -> NextAction {
+        if self.paused {
+            return NextAction::Pause;
+        }
+        if self.operations.is_empty() {
+            return NextAction::SubmitEmpty;
+        }
+        let weights = self
+            .operations
+            .values()
+            .map(|settings| f64::from(settings.weight));
+        let index = WeightedIndex::new(weights)
+            .expect("Weights are positive")
+            .sample(&mut self.rng);
+        let op = *self
+            .operations
+            .keys()
+            .nth(index)
+            .expect("Sampled index is in bounds");
+        NextAction::Perform(op, self.next_index)
+    }
+
+    /// Marks one transaction of the operation as scheduled to be sent. Counts are used up
+    /// on scheduling, so a transaction which fails to be submitted is not retried.
+    pub fn operation_is_scheduled(&mut self, op: Operation) {
+        self.next_index += 1;
+        if let Some(settings) = self.operations.get_mut(&op) {
+            if let Some(count) = settings.count.as_mut() {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    self.operations.remove(&op);
+                    if self.rate_from_operations && !self.operations.is_empty() {
+                        self.shape = self.operations_tps().into();
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn encode_metrics(&self, encoder: &mut Encoder) {
+        encoder
+            .family(
+                "target_tps",
+                MetricKind::Gauge,
+                "Rate the load shape gives at the moment.",
+            )
+            .sample("target_tps", &[], self.target_tps);
+        encoder
+            .family("paused", MetricKind::Gauge, "Whether the load is paused.")
+            .sample("paused", &[], u8::from(self.paused));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resumed_progress_continues_the_sequence() {
+        let tps = PositiveFloat::ONE;
+        let mut settings = LoadSettings::new(tps.into(), Arrival::Fixed, Some(0), BTreeMap::new());
+        assert_eq!(settings.take_sequence(), 0);
+        assert_eq!(settings.take_sequence(), 1);
+        let mut resumed = LoadSettings::new(tps.into(), Arrival::Fixed, Some(0), BTreeMap::new());
+        resumed.restore_progress(
+            settings.operations().clone(),
+            settings.next_index(),
+            settings.next_sequence(),
+        );
+        assert_eq!(resumed.take_sequence(), 2);
+    }
+
+    #[test]
+    fn stage_rate_drops_when_an_operation_is_used_up() {
+        let operation = |weight, count| OperationSettings {
+            weight: PositiveFloat::new(weight).expect("Positive weight"),
+            count: Some(count),
+        };
+        let operations = [
+            (Operation::RegisterDomain, operation(3.0, 10)),
+            (Operation::RegisterAccount, operation(1.0, 1)),
+        ]
+        .into_iter()
+        .collect();
+        let mut settings = LoadSettings::new(
+            PositiveFloat::ONE.into(),
+            Arrival::Fixed,
+            Some(0),
+            BTreeMap::new(),
+        );
+        settings.start_stage("s".to_owned(), None, operations);
+        assert_eq!(settings.next_interval(), Duration::from_millis(250));
+        settings.operation_is_scheduled(Operation::RegisterAccount);
+        assert_eq!(settings.target_tps(), 4.0);
+        let interval = settings.next_interval();
+        assert_eq!(settings.target_tps(), 3.0);
+        assert_eq!(interval, Duration::from_secs_f64(1.0 / 3.0));
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, WrapErr as _};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{BufRead as _, BufReader, Write as _},
+    path::PathBuf,
+};
+use tracing::warn;
+
+/// Summary of the status at some moment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub at: DateTime<Utc>,
+    pub uptime_secs: i64,
+    pub txs_sent: usize,
+    pub txs_committed: usize,
+    pub txs_rejected: usize,
+    pub txs_failed: usize,
+    pub txs_timed_out: usize,
+    pub txs_in_flight: usize,
+    /// Rates over the last minute.
+    pub sent_tps: f64,
+    pub committed_tps: f64,
+    pub rejected_tps: f64,
+    pub target_tps: f64,
+    pub commit_latency_p50_ms: Option<f64>,
+    pub commit_latency_p99_ms: Option<f64>,
+}
+
+/// Columns of the CSV representation, in the order of the fields of [`Snapshot`].
+const CSV_HEADER: &str = "at,uptime_secs,txs_sent,txs_committed,txs_rejected,txs_failed,\
+txs_timed_out,txs_in_flight,sent_tps,committed_tps,rejected_tps,target_tps,\
+commit_latency_p50_ms,commit_latency_p99_ms";
+
+/// Ring buffer of the latest snapshots, optionally appended to a file as JSON lines.
+#[derive(Debug)]
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+    file: Option<File>,
+}
+
+impl History {
+    /// Makes a history keeping `capacity` snapshots. If a file is given, the snapshots
+    /// it already has are loaded, so the history survives restarts.
+    ///
+    /// # Errors
+    /// if the file can't be opened.
+    pub fn new(capacity: usize, path: Option<PathBuf>) -> Result<Self> {
+        let mut history = Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+            file: None,
+        };
+        if let Some(path) = path {
+            if path.exists() {
+                let file = File::open(&path).wrap_err_with(|| {
+                    format!("Failed to open the history file `{}`", path.display())
+                })?;
+                for line in BufReader::new(file).lines() {
+                    let line = line.wrap_err("Failed to read the history file")?;
+                    match serde_json::from_str(&line) {
+                        Ok(snapshot) => history.push(snapshot),
+                        Err(err) => warn!("Skipping a malformed history line: {}", err),
+                    }
+                }
+            }
+            history.file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .wrap_err_with(|| {
+                        format!("Failed to open the history file `{}`", path.display())
+                    })?,
+            );
+        }
+        Ok(history)
+    }
+
+    pub fn record(&mut self, snapshot: Snapshot) {
+        if let Some(file) = &mut self.file {
+            let line = serde_json::to_string(&snapshot).expect("Failed to serialize a snapshot");
+            if let Err(err) = writeln!(file, "{}", line) {
+                warn!("Failed to persist a snapshot: {}", err);
+            }
+        }
+        self.push(snapshot);
+    }
+
+    fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Returns the snapshots taken within the bounds, which are inclusive.
+    pub fn range(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> impl Iterator<Item = &Snapshot> {
+        self.snapshots.iter().filter(move |snapshot| {
+            !matches!(from, Some(from) if snapshot.at < from)
+                && !matches!(to, Some(to) if snapshot.at > to)
+        })
+    }
+}
+
+/// Formats the snapshots as CSV with a header.
+pub fn to_csv<'a>(snapshots: impl IntoIterator<Item = &'a Snapshot>) -> String {
+    let optional = |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for snapshot in snapshots {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            snapshot.at.to_rfc3339(),
+            snapshot.uptime_secs,
+            snapshot.txs_sent,
+            snapshot.txs_committed,
+            snapshot.txs_rejected,
+            snapshot.txs_failed,
+            snapshot.txs_timed_out,
+            snapshot.txs_in_flight,
+            snapshot.sent_tps,
+            snapshot.committed_tps,
+            snapshot.rejected_tps,
+            snapshot.target_tps,
+            optional(snapshot.commit_latency_p50_ms),
+            optional(snapshot.commit_latency_p99_ms),
+        )
+        .expect("Writing to a string never fails");
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(at: &str) -> Snapshot {
+        Snapshot {
+            at: DateTime::parse_from_rfc3339(at)
+                .expect("Valid time")
+                .with_timezone(&Utc),
+            uptime_secs: 60,
+            txs_sent: 10,
+            txs_committed: 8,
+            txs_rejected: 1,
+            txs_failed: 0,
+            txs_timed_out: 1,
+            txs_in_flight: 0,
+            sent_tps: 0.5,
+            committed_tps: 0.25,
+            rejected_tps: 0.0,
+            target_tps: 1.0,
+            commit_latency_p50_ms: Some(12.5),
+            commit_latency_p99_ms: None,
+        }
+    }
+
+    fn time(at: &str) -> Option<DateTime<Utc>> {
+        Some(snapshot(at).at)
+    }
+
+    fn history() -> History {
+        let mut history = History::new(3, None).expect("History without a file");
+        for at in [
+            "2024-01-01T00:00:00Z",
+            "2024-01-01T01:00:00Z",
+            "2024-01-01T02:00:00Z",
+            "2024-01-01T03:00:00Z",
+        ] {
+            history.record(snapshot(at));
+        }
+        history
+    }
+
+    #[test]
+    fn keeps_the_latest_snapshots() {
+        let history = history();
+        let kept = history.range(None, None).map(|s| s.at).collect::<Vec<_>>();
+        assert_eq!(
+            kept,
+            [
+                time("2024-01-01T01:00:00Z").unwrap(),
+                time("2024-01-01T02:00:00Z").unwrap(),
+                time("2024-01-01T03:00:00Z").unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn range_bounds_are_inclusive() {
+        let history = history();
+        let count = |from, to| history.range(time(from), time(to)).count();
+        assert_eq!(count("2024-01-01T01:00:00Z", "2024-01-01T02:00:00Z"), 2);
+        assert_eq!(count("2024-01-01T01:30:00Z", "2024-01-01T02:30:00Z"), 1);
+        assert_eq!(count("2024-01-01T04:00:00Z", "2024-01-01T05:00:00Z"), 0);
+        assert_eq!(history.range(time("2024-01-01T02:00:00Z"), None).count(), 2);
+        assert_eq!(history.range(None, time("2024-01-01T01:00:00Z")).count(), 1);
+    }
+
+    #[test]
+    fn csv_has_a_header_and_a_row_per_snapshot() {
+        let csv = to_csv(&[snapshot("2024-01-01T00:00:00Z")]);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            "2024-01-01T00:00:00+00:00,60,10,8,1,0,1,0,0.5,0.25,0,1,12.5,"
+        );
+        assert_eq!(lines[0].split(',').count(), lines[1].split(',').count());
+    }
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+/// Amount of linear sub-buckets in every power-of-two range, which bounds
+/// the relative error of a recorded value by `1 / SUB_BUCKETS`.
+const SUB_BUCKETS: u64 = 16;
+const SUB_BUCKET_BITS: u32 = SUB_BUCKETS.trailing_zeros();
+
+/// Log-linear histogram of latencies with microsecond resolution.
+///
+/// Memory is bounded regardless of the amount of recorded values, so it can be
+/// kept for the whole duration of a longevity run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(into = "LatencyReport", from = "LatencyReport")]
+pub struct LatencyHistogram {
+    buckets: BTreeMap<usize, u64>,
+    count: u64,
+    sum_us: u128,
+    min_us: Option<u64>,
+    max_us: Option<u64>,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let value = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        *self.buckets.entry(bucket_index(value)).or_default() += 1;
+        self.count += 1;
+        self.sum_us += u128::from(value);
+        self.min_us = Some(self.min_us.map_or(value, |min| min.min(value)));
+        self.max_us = Some(self.max_us.map_or(value, |max| max.max(value)));
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(u64::try_from(self.sum_us).unwrap_or(u64::MAX))
+    }
+
+    /// Returns the amount of recorded values whose bucket lies entirely below the bound.
+    /// Values in the bucket containing the bound are left out, so the count is exact
+    /// only for bounds at bucket edges, see [`Self::bucket_edge`].
+    pub fn count_below(&self, bound: Duration) -> u64 {
+        let bound = u64::try_from(bound.as_micros()).unwrap_or(u64::MAX);
+        self.buckets
+            .iter()
+            .take_while(|(index, _)| bucket_upper_bound(**index) <= bound)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Returns the upper edge of the bucket containing the bound, the least bound
+    /// not below it for which [`Self::count_below`] is exact.
+    pub fn bucket_edge(bound: Duration) -> Duration {
+        let bound = u64::try_from(bound.as_micros()).unwrap_or(u64::MAX);
+        Duration::from_micros(bucket_upper_bound(bucket_index(bound)))
+    }
+
+    /// Returns the upper bound of the bucket containing the given quantile
+    /// (`0.0..=1.0`), clamped by the maximum recorded value.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                let value = bucket_upper_bound(*index).min(self.max_us.unwrap_or(u64::MAX));
+                return Some(Duration::from_micros(value));
+            }
+        }
+        self.max_us.map(Duration::from_micros)
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min_us.map(Duration::from_micros)
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.max_us.map(Duration::from_micros)
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count != 0).then(|| {
+            let mean = self.sum_us / u128::from(self.count);
+            Duration::from_micros(u64::try_from(mean).unwrap_or(u64::MAX))
+        })
+    }
+}
+
+fn bucket_index(value: u64) -> usize {
+    if value < 2 * SUB_BUCKETS {
+        return value as usize;
+    }
+    let magnitude = u64::BITS - 1 - value.leading_zeros();
+    let shift = magnitude - SUB_BUCKET_BITS;
+    let sub_bucket = (value >> shift) & (SUB_BUCKETS - 1);
+    ((u64::from(shift) + 1) * SUB_BUCKETS + sub_bucket) as usize
+}
+
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < 2 * SUB_BUCKETS {
+        return index;
+    }
+    let shift = index / SUB_BUCKETS - 1;
+    let sub_bucket = index % SUB_BUCKETS;
+    let lower = (SUB_BUCKETS + sub_bucket) << shift;
+    lower.saturating_add((1 << shift) - 1)
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// Serialized form of [`LatencyHistogram`]: a human readable summary in
+/// milliseconds followed by the non-empty buckets keyed by their upper bound.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LatencyReport {
+    count: u64,
+    min_ms: Option<f64>,
+    mean_ms: Option<f64>,
+    p50_ms: Option<f64>,
+    p90_ms: Option<f64>,
+    p99_ms: Option<f64>,
+    max_ms: Option<f64>,
+    buckets_us: Vec<(u64, u64)>,
+}
+
+impl From<LatencyHistogram> for LatencyReport {
+    fn from(histogram: LatencyHistogram) -> Self {
+        Self {
+            count: histogram.count,
+            min_ms: histogram.min().map(as_millis),
+            mean_ms: histogram.mean().map(as_millis),
+            p50_ms: histogram.quantile(0.5).map(as_millis),
+            p90_ms: histogram.quantile(0.9).map(as_millis),
+            p99_ms: histogram.quantile(0.99).map(as_millis),
+            max_ms: histogram.max().map(as_millis),
+            buckets_us: histogram
+                .buckets
+                .iter()
+                .map(|(index, count)| (bucket_upper_bound(*index), *count))
+                .collect(),
+        }
+    }
+}
+
+impl From<LatencyReport> for LatencyHistogram {
+    fn from(report: LatencyReport) -> Self {
+        let from_millis = |ms: f64| (ms * 1000.0).round() as u64;
+        let buckets = report
+            .buckets_us
+            .into_iter()
+            .map(|(upper_bound, count)| (bucket_index(upper_bound), count))
+            .collect();
+        Self {
+            buckets,
+            count: report.count,
+            sum_us: report.mean_ms.map_or(0, |mean| {
+                u128::from(from_millis(mean)) * u128::from(report.count)
+            }),
+            min_us: report.min_ms.map(from_millis),
+            max_us: report.max_ms.map(from_millis),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram_of(latencies_us: impl IntoIterator<Item = u64>) -> LatencyHistogram {
+        let mut histogram = LatencyHistogram::default();
+        for latency in latencies_us {
+            histogram.record(Duration::from_micros(latency));
+        }
+        histogram
+    }
+
+    #[test]
+    fn empty_histogram_has_no_statistics() {
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.quantile(0.5), None);
+        assert_eq!(histogram.min(), None);
+        assert_eq!(histogram.mean(), None);
+    }
+
+    #[test]
+    fn small_values_are_exact() {
+        let histogram = histogram_of(0..2 * SUB_BUCKETS);
+        for value in 0..2 * SUB_BUCKETS {
+            assert_eq!(bucket_upper_bound(bucket_index(value)), value);
+        }
+        assert_eq!(histogram.min(), Some(Duration::from_micros(0)));
+        assert_eq!(histogram.max(), Some(Duration::from_micros(31)));
+    }
+
+    #[test]
+    fn buckets_contain_their_values() {
+        for value in [32, 33, 100, 1_000, 65_535, 1_000_000, u64::MAX / 2] {
+            let upper_bound = bucket_upper_bound(bucket_index(value));
+            assert!(upper_bound >= value);
+            assert!(upper_bound - value <= value / SUB_BUCKETS);
+        }
+    }
+
+    #[test]
+    fn quantiles_are_within_relative_error() {
+        let histogram = histogram_of(1..=1_000);
+        let p50 = histogram.quantile(0.5).expect("Histogram is not empty");
+        let p99 = histogram.quantile(0.99).expect("Histogram is not empty");
+        assert!((500..=500 + 500 / SUB_BUCKETS as u128).contains(&p50.as_micros()));
+        assert!((990..=1_000).contains(&p99.as_micros()));
+        assert_eq!(histogram.quantile(1.0), Some(Duration::from_micros(1_000)));
+        assert_eq!(histogram.mean(), Some(Duration::from_micros(500)));
+    }
+
+    #[test]
+    fn count_below_is_exact_at_bucket_edges() {
+        let histogram = histogram_of(4_990..=5_200);
+        let edge = LatencyHistogram::bucket_edge(Duration::from_micros(5_000));
+        assert!(edge >= Duration::from_micros(5_000));
+        let below_edge = (4_990..=5_200)
+            .filter(|value| *value <= edge.as_micros())
+            .count() as u64;
+        assert_eq!(histogram.count_below(edge), below_edge);
+        assert!(histogram.count_below(Duration::from_micros(5_000)) <= below_edge);
+        assert_eq!(LatencyHistogram::bucket_edge(edge), edge);
+    }
+
+    #[test]
+    fn report_round_trip_keeps_buckets() {
+        let histogram = histogram_of([10, 200, 3_000, 40_000]);
+        let restored = LatencyHistogram::from(LatencyReport::from(histogram.clone()));
+        assert_eq!(restored.count(), histogram.count());
+        assert_eq!(restored.buckets, histogram.buckets);
+        assert_eq!(restored.quantile(0.5), histogram.quantile(0.5));
+    }
+}
@@ -0,0 +1,264 @@
+use crate::number::PositiveFloat;
+use color_eyre::eyre::{bail, eyre, Report, Result, WrapErr as _};
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::PI, str::FromStr, time::Duration};
+
+/// The lowest target rate a shape can give, so the next send is never infinitely far.
+const MIN_TPS: f64 = 0.01;
+
+/// Target rate changing over time since the shape was applied.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind", deny_unknown_fields)]
+pub enum LoadShape {
+    Constant {
+        tps: PositiveFloat,
+    },
+    /// Linear change from `from` to `to` during `duration_secs`, then `to` is kept.
+    Ramp {
+        from: PositiveFloat,
+        to: PositiveFloat,
+        duration_secs: PositiveFloat,
+    },
+    /// Staircase from `from` to `to` in `steps` equal steps lasting `step_secs` each,
+    /// then `to` is kept.
+    Steps {
+        from: PositiveFloat,
+        to: PositiveFloat,
+        steps: usize,
+        step_secs: PositiveFloat,
+    },
+    /// Sine wave around `mean` with the given amplitude and period.
+    Sine {
+        mean: PositiveFloat,
+        amplitude: PositiveFloat,
+        period_secs: PositiveFloat,
+    },
+    /// `base` rate with a `peak` lasting `spike_secs` at the end of every period.
+    Spike {
+        base: PositiveFloat,
+        peak: PositiveFloat,
+        period_secs: PositiveFloat,
+        spike_secs: PositiveFloat,
+    },
+}
+
+impl LoadShape {
+    /// Checks the parameters: the amplitude of a sine has to be less than its mean, as a rate
+    /// dropping to [`MIN_TPS`] would stall the load, and a spike has to fit into its period.
+    ///
+    /// # Errors
+    /// if the shape is invalid.
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            LoadShape::Sine {
+                mean, amplitude, ..
+            } if f64::from(amplitude) >= f64::from(mean) => {
+                bail!("Sine amplitude has to be less than its mean")
+            }
+            LoadShape::Spike {
+                period_secs,
+                spike_secs,
+                ..
+            } if f64::from(spike_secs) > f64::from(period_secs) => {
+                bail!("Spike can't last longer than its period")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the target rate after `elapsed` time since the shape was applied.
+    pub fn tps_at(&self, elapsed: Duration) -> f64 {
+        let elapsed = elapsed.as_secs_f64();
+        let tps = match *self {
+            LoadShape::Constant { tps } => tps.into(),
+            LoadShape::Ramp {
+                from,
+                to,
+                duration_secs,
+            } => {
+                let (from, to) = (f64::from(from), f64::from(to));
+                let progress = (elapsed / f64::from(duration_secs)).min(1.0);
+                from + (to - from) * progress
+            }
+            LoadShape::Steps {
+                from,
+                to,
+                steps,
+                step_secs,
+            } => {
+                let (from, to) = (f64::from(from), f64::from(to));
+                if steps == 0 {
+                    to
+                } else {
+                    let step = (elapsed / f64::from(step_secs)).floor().min(steps as f64);
+                    from + (to - from) * step / steps as f64
+                }
+            }
+            LoadShape::Sine {
+                mean,
+                amplitude,
+                period_secs,
+            } => {
+                let phase = 2.0 * PI * elapsed / f64::from(period_secs);
+                f64::from(mean) + f64::from(amplitude) * phase.sin()
+            }
+            LoadShape::Spike {
+                base,
+                peak,
+                period_secs,
+                spike_secs,
+            } => {
+                let period = f64::from(period_secs);
+                if elapsed % period >= period - f64::from(spike_secs) {
+                    peak.into()
+                } else {
+                    base.into()
+                }
+            }
+        };
+        tps.max(MIN_TPS)
+    }
+}
+
+impl From<PositiveFloat> for LoadShape {
+    fn from(tps: PositiveFloat) -> Self {
+        LoadShape::Constant { tps }
+    }
+}
+
+/// Parses the shape given on the command line as `KIND:PARAM:...`:
+///
+/// - `constant:TPS`
+/// - `ramp:FROM:TO:DURATION_SECS`
+/// - `steps:FROM:TO:STEPS:STEP_SECS`
+/// - `sine:MEAN:AMPLITUDE:PERIOD_SECS`
+/// - `spike:BASE:PEAK:PERIOD_SECS:SPIKE_SECS`
+impl FromStr for LoadShape {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, params) = s.split_once(':').unwrap_or((s, ""));
+        let params = params.split(':').collect::<Vec<_>>();
+        let float = |index: usize| -> Result<PositiveFloat> {
+            let param = params
+                .get(index)
+                .ok_or_else(|| eyre!("Missing parameter {} of `{}`", index + 1, s))?;
+            PositiveFloat::from_str(param)
+                .wrap_err_with(|| format!("Invalid parameter {} of `{}`", index + 1, s))
+        };
+        let expect_params = |count: usize| -> Result<()> {
+            if params.len() != count {
+                bail!("Shape `{}` expects {} parameters", kind, count);
+            }
+            Ok(())
+        };
+        let shape = match kind {
+            "constant" => {
+                expect_params(1)?;
+                LoadShape::Constant { tps: float(0)? }
+            }
+            "ramp" => {
+                expect_params(3)?;
+                LoadShape::Ramp {
+                    from: float(0)?,
+                    to: float(1)?,
+                    duration_secs: float(2)?,
+                }
+            }
+            "steps" => {
+                expect_params(4)?;
+                LoadShape::Steps {
+                    from: float(0)?,
+                    to: float(1)?,
+                    steps: params[2]
+                        .parse()
+                        .wrap_err_with(|| format!("Invalid amount of steps in `{}`", s))?,
+                    step_secs: float(3)?,
+                }
+            }
+            "sine" => {
+                expect_params(3)?;
+                LoadShape::Sine {
+                    mean: float(0)?,
+                    amplitude: float(1)?,
+                    period_secs: float(2)?,
+                }
+            }
+            "spike" => {
+                expect_params(4)?;
+                LoadShape::Spike {
+                    base: float(0)?,
+                    peak: float(1)?,
+                    period_secs: float(2)?,
+                    spike_secs: float(3)?,
+                }
+            }
+            _ => bail!("Unknown load shape `{}`", kind),
+        };
+        shape.validate()?;
+        Ok(shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(s: &str) -> LoadShape {
+        s.parse().expect("Valid shape")
+    }
+
+    fn tps_at(shape: &LoadShape, secs: f64) -> f64 {
+        shape.tps_at(Duration::from_secs_f64(secs))
+    }
+
+    #[test]
+    fn ramp_goes_linearly_and_keeps_the_end_rate() {
+        let ramp = shape("ramp:10:20:100");
+        assert_eq!(tps_at(&ramp, 0.0), 10.0);
+        assert_eq!(tps_at(&ramp, 50.0), 15.0);
+        assert_eq!(tps_at(&ramp, 100.0), 20.0);
+        assert_eq!(tps_at(&ramp, 1000.0), 20.0);
+    }
+
+    #[test]
+    fn steps_change_at_step_boundaries() {
+        let steps = shape("steps:10:40:3:60");
+        assert_eq!(tps_at(&steps, 59.0), 10.0);
+        assert_eq!(tps_at(&steps, 60.0), 20.0);
+        assert_eq!(tps_at(&steps, 150.0), 30.0);
+        assert_eq!(tps_at(&steps, 1000.0), 40.0);
+    }
+
+    #[test]
+    fn sine_oscillates_around_the_mean() {
+        let sine = shape("sine:10:5:100");
+        assert!((tps_at(&sine, 0.0) - 10.0).abs() < 1e-9);
+        assert!((tps_at(&sine, 25.0) - 15.0).abs() < 1e-9);
+        assert!((tps_at(&sine, 75.0) - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn spike_comes_at_the_end_of_every_period() {
+        let spike = shape("spike:1:100:60:10");
+        assert_eq!(tps_at(&spike, 0.0), 1.0);
+        assert_eq!(tps_at(&spike, 49.0), 1.0);
+        assert_eq!(tps_at(&spike, 55.0), 100.0);
+        assert_eq!(tps_at(&spike, 61.0), 1.0);
+    }
+
+    #[test]
+    fn rejects_invalid_shapes() {
+        for s in [
+            "constant",
+            "constant:0",
+            "ramp:1:2",
+            "sine:10:10:60",
+            "sine:10:20:60",
+            "spike:1:100:60:61",
+            "wave:1",
+        ] {
+            assert!(s.parse::<LoadShape>().is_err(), "{}", s);
+        }
+    }
+}
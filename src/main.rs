@@ -1,9 +1,23 @@
-//! Script assumes that no other scripts or clients are generating transactions.
+//! Longevity load script for Iroha 2: submits transactions to the network for a long time
+//! and reports what became of them.
 mod args;
+mod arrival;
 mod async_client;
+mod checkpoint;
 mod commands;
+mod config;
+mod control;
+mod history;
+mod latency;
+mod load_shape;
+mod metrics;
 mod number;
 mod operation;
+mod rate;
+mod reconcile;
+mod rejection;
+mod run_id;
+mod scenario;
 mod status;
 mod value;
 
@@ -21,6 +35,9 @@ use structopt::StructOpt;
 enum Args {
     Oneshot(commands::oneshot::Args),
     Daemon(commands::daemon::Args),
+    Run(commands::run::Args),
+    Cleanup(commands::cleanup::Args),
+    Reconcile(commands::reconcile::Args),
 }
 
 #[async_trait]
@@ -29,6 +46,9 @@ impl RunArgs for Args {
         match self {
             Args::Oneshot(comm) => comm.run(writer).await,
             Args::Daemon(comm) => comm.run(writer).await,
+            Args::Run(comm) => comm.run(writer).await,
+            Args::Cleanup(comm) => comm.run(writer).await,
+            Args::Reconcile(comm) => comm.run(writer).await,
         }
     }
 }
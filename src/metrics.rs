@@ -0,0 +1,112 @@
+use crate::latency::LatencyHistogram;
+use std::{
+    fmt::{Display, Write as _},
+    time::Duration,
+};
+
+/// Common prefix of all exported metric names.
+const PREFIX: &str = "iroha2_load_";
+
+/// Bucket bounds (in seconds) of the exported latency histograms, exported
+/// at the nearest edges of [`LatencyHistogram`] buckets above them.
+const LATENCY_BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0,
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+            MetricKind::Histogram => "histogram",
+        }
+    }
+}
+
+/// Builder of a document in the Prometheus text exposition format.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    buffer: String,
+}
+
+impl Encoder {
+    /// Starts a new metric family. Samples of the family have to follow it.
+    pub fn family(&mut self, name: &str, kind: MetricKind, help: &str) -> &mut Self {
+        writeln!(self.buffer, "# HELP {}{} {}", PREFIX, name, help).expect("Failed to write help");
+        writeln!(self.buffer, "# TYPE {}{} {}", PREFIX, name, kind.as_str())
+            .expect("Failed to write type");
+        self
+    }
+
+    pub fn sample(
+        &mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        value: impl Display,
+    ) -> &mut Self {
+        writeln!(
+            self.buffer,
+            "{}{}{} {}",
+            PREFIX,
+            name,
+            format_labels(labels.iter().copied()),
+            value
+        )
+        .expect("Failed to write sample");
+        self
+    }
+
+    /// Writes bucket, sum and count samples of a histogram family.
+    pub fn histogram(
+        &mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        histogram: &LatencyHistogram,
+    ) -> &mut Self {
+        let bucket_name = format!("{}_bucket", name);
+        for bound in LATENCY_BUCKETS {
+            // Bounds are moved to the edges of histogram buckets, where counts are exact
+            let edge = LatencyHistogram::bucket_edge(Duration::from_secs_f64(bound));
+            let count = histogram.count_below(edge);
+            let bound = edge.as_secs_f64().to_string();
+            let labels = [labels, &[("le", bound.as_str())]].concat();
+            self.sample(&bucket_name, &labels, count);
+        }
+        let labels_inf = [labels, &[("le", "+Inf")]].concat();
+        self.sample(&bucket_name, &labels_inf, histogram.count());
+        self.sample(
+            &format!("{}_sum", name),
+            labels,
+            histogram.sum().as_secs_f64(),
+        );
+        self.sample(&format!("{}_count", name), labels, histogram.count())
+    }
+
+    pub fn finish(self) -> String {
+        self.buffer
+    }
+}
+
+fn format_labels<'a>(labels: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    let labels = labels
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', r"\\")
+                .replace('"', "\\\"")
+                .replace('\n', r"\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect::<Vec<_>>();
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
@@ -1,9 +1,31 @@
-use serde::{de::Visitor, Deserialize, Deserializer};
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
 use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone)]
 pub struct PositiveFloat(f64);
 
+impl PositiveFloat {
+    pub const ONE: Self = Self(1.0);
+
+    pub fn new(value: f64) -> Option<Self> {
+        (value > 0.0).then_some(Self(value))
+    }
+}
+
+/// Weight of peers and operations which are given none.
+pub fn default_weight() -> PositiveFloat {
+    PositiveFloat::ONE
+}
+
+impl Serialize for PositiveFloat {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_f64(self.0)
+    }
+}
+
 impl<'de> Deserialize<'de> for PositiveFloat {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -42,6 +64,20 @@ impl<'de> Visitor<'de> for F64Visitor {
             Err(E::custom("can't be a negative float or zero"))
         }
     }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_f64(v as f64)
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_f64(v as f64)
+    }
 }
 
 impl From<PositiveFloat> for f64 {
@@ -1,6 +1,15 @@
+use crate::{
+    control::OperationSettings,
+    number::{default_weight, PositiveFloat},
+};
+use color_eyre::eyre::{eyre, Report, Result, WrapErr as _};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use strum_macros::EnumString;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, EnumString, Serialize, Deserialize,
+)]
 #[allow(clippy::enum_variant_names)]
 pub enum Operation {
     RegisterAccount,
@@ -12,3 +21,102 @@ pub enum Operation {
     TransferAsset,
     MintAsset,
 }
+
+/// Operation given on the command line as `NAME[:WEIGHT[:COUNT]]`, where `COUNT`
+/// is either an amount of transactions or `unlimited`.
+#[derive(Debug, Clone, Copy)]
+pub struct OperationArg {
+    pub operation: Operation,
+    pub weight: Option<PositiveFloat>,
+    pub count: Option<Count>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Count {
+    Limited(usize),
+    Unlimited,
+}
+
+impl OperationArg {
+    /// Makes settings of the operation, falling back to the given default count.
+    pub fn settings(&self, default_count: usize) -> OperationSettings {
+        OperationSettings {
+            weight: self.weight.unwrap_or_else(default_weight),
+            count: match self.count {
+                Some(Count::Limited(count)) => Some(count),
+                Some(Count::Unlimited) => None,
+                None => Some(default_count),
+            },
+        }
+    }
+}
+
+impl FromStr for OperationArg {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(':');
+        let operation = parts
+            .next()
+            .unwrap_or_default()
+            .parse()
+            .wrap_err_with(|| format!("Unknown operation in `{}`", s))?;
+        let weight = parts
+            .next()
+            .map(PositiveFloat::from_str)
+            .transpose()
+            .wrap_err_with(|| format!("Invalid weight in `{}`", s))?;
+        let count = parts
+            .next()
+            .map(|count| match count {
+                "unlimited" => Ok(Count::Unlimited),
+                count => count.parse().map(Count::Limited),
+            })
+            .transpose()
+            .wrap_err_with(|| format!("Invalid count in `{}`", s))?;
+        if parts.next().is_some() {
+            return Err(eyre!("Expected `NAME[:WEIGHT[:COUNT]]`, got `{}`", s));
+        }
+        Ok(Self {
+            operation,
+            weight,
+            count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_operation_with_weight_and_count() {
+        let arg: OperationArg = "TransferAsset:7:unlimited"
+            .parse()
+            .expect("Valid operation argument");
+        assert_eq!(arg.operation, Operation::TransferAsset);
+        let settings = arg.settings(100);
+        assert_eq!(f64::from(settings.weight), 7.0);
+        assert_eq!(settings.count, None);
+    }
+
+    #[test]
+    fn falls_back_to_default_weight_and_count() {
+        let arg: OperationArg = "MintAsset".parse().expect("Valid operation argument");
+        let settings = arg.settings(100);
+        assert_eq!(f64::from(settings.weight), 1.0);
+        assert_eq!(settings.count, Some(100));
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        for arg in [
+            "Unknown",
+            "MintAsset:0",
+            "MintAsset:1:many",
+            "MintAsset:1:2:3",
+        ] {
+            assert!(arg.parse::<OperationArg>().is_err(), "{}", arg);
+        }
+    }
+}
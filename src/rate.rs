@@ -0,0 +1,116 @@
+use chrono::Utc;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use std::collections::VecDeque;
+
+/// Windows (in seconds) over which rates are reported.
+const WINDOWS_SECS: [i64; 4] = [10, 60, 300, 900];
+/// Amount of per-second buckets kept, enough to cover the longest window.
+const HORIZON_SECS: i64 = 900;
+
+/// Counts events per second of wall clock time to report their recent rates.
+#[derive(Debug, Clone, Default)]
+pub struct RateMeter {
+    /// Pairs of a UNIX timestamp in seconds and the amount of events during that second.
+    buckets: VecDeque<(i64, u64)>,
+}
+
+impl RateMeter {
+    /// Counts an event during the current second, which is returned so the event can be
+    /// taken back with [`Self::unrecord`].
+    pub fn record(&mut self) -> i64 {
+        let now = Utc::now().timestamp();
+        match self.buckets.back_mut() {
+            Some((second, count)) if *second == now => *count += 1,
+            _ => self.buckets.push_back((now, 1)),
+        }
+        while matches!(self.buckets.front(), Some((second, _)) if *second <= now - HORIZON_SECS) {
+            self.buckets.pop_front();
+        }
+        now
+    }
+
+    /// Takes back an event recorded during the given second.
+    pub fn unrecord(&mut self, second: i64) {
+        if let Some((_, count)) = self.buckets.iter_mut().find(|(at, _)| *at == second) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Returns the average rate per second over the completed seconds of the window.
+    pub fn rate(&self, window_secs: i64) -> f64 {
+        self.rate_at(window_secs, Utc::now().timestamp())
+    }
+
+    /// Returns the rate over the window as of the given UNIX timestamp in seconds.
+    fn rate_at(&self, window_secs: i64, now: i64) -> f64 {
+        let count: u64 = self
+            .buckets
+            .iter()
+            .filter(|(second, _)| (now - window_secs..now).contains(second))
+            .map(|(_, count)| count)
+            .sum();
+        count as f64 / window_secs as f64
+    }
+}
+
+impl Serialize for RateMeter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(WINDOWS_SECS.len()))?;
+        for window in WINDOWS_SECS {
+            let label = if window % 60 == 0 {
+                format!("{}m", window / 60)
+            } else {
+                format!("{}s", window)
+            };
+            map.serialize_entry(&label, &self.rate(window))?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrecord_takes_back_the_event_of_its_second() {
+        let mut meter = RateMeter::default();
+        let second = meter.record();
+        meter.buckets.push_back((second + 1, 3));
+        meter.unrecord(second);
+        assert_eq!(meter.buckets, [(second, 0), (second + 1, 3)]);
+    }
+
+    #[test]
+    fn unrecord_of_a_forgotten_second_does_nothing() {
+        let mut meter = RateMeter::default();
+        let second = meter.record();
+        meter.unrecord(second - HORIZON_SECS);
+        assert_eq!(meter.buckets, [(second, 1)]);
+    }
+
+    #[test]
+    fn rate_counts_only_completed_seconds_of_the_window() {
+        let now = 1_000_000;
+        let meter = RateMeter {
+            buckets: [(now - 11, 100), (now - 10, 10), (now - 1, 5), (now, 7)]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(meter.rate_at(10, now), 1.5);
+        assert_eq!(meter.rate_at(60, now), 115.0 / 60.0);
+        assert_eq!(meter.rate_at(10, now + 20), 0.0);
+    }
+
+    #[test]
+    fn rates_are_reported_per_window() {
+        let json = serde_json::to_value(RateMeter::default()).expect("Failed to serialize");
+        assert_eq!(
+            json,
+            serde_json::json!({ "10s": 0.0, "1m": 0.0, "5m": 0.0, "15m": 0.0 })
+        );
+    }
+}
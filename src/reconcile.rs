@@ -0,0 +1,250 @@
+use crate::{
+    async_client::{Client, RunTransaction},
+    latency::LatencyHistogram,
+    run_id::RunId,
+};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use iroha_crypto::Hash;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+/// Extra time before the start of a scanned period, as blocks are timestamped by peers.
+pub const CLOCK_SKEW_SECS: i64 = 5;
+
+/// What the live event stream said about the sent transactions of a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveCounters {
+    /// Start of the run, blocks since then have to be scanned to compare them with the counters.
+    pub started_at: DateTime<Utc>,
+    pub txs_sent: usize,
+    pub txs_committed: usize,
+    pub txs_rejected: usize,
+    pub txs_timed_out: usize,
+    pub txs_in_flight: usize,
+    /// Hashes of the sent transactions without a live outcome that are still known.
+    #[serde(skip)]
+    pub unresolved: Vec<Hash>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Outcomes {
+    committed: usize,
+    rejected: usize,
+}
+
+impl Outcomes {
+    fn count(&mut self, is_rejected: bool) {
+        if is_rejected {
+            self.rejected += 1;
+        } else {
+            self.committed += 1;
+        }
+    }
+}
+
+/// Transactions without a live outcome by what the blocks say about them.
+#[derive(Debug, Default, Serialize)]
+pub struct Resolution {
+    committed: Vec<Hash>,
+    rejected: Vec<Hash>,
+    missing: Vec<Hash>,
+}
+
+/// Outcomes of the transactions of a run found in the blocks, compared with the live counters.
+#[derive(Debug, Serialize)]
+pub struct ReconcileReport {
+    run_id: RunId,
+    since: Option<DateTime<Utc>>,
+    blocks_scanned: usize,
+    found: Outcomes,
+    /// Found outcomes by the operation in the metadata of transactions.
+    operations: BTreeMap<String, Outcomes>,
+    /// Time from sending a transaction to the creation of the block committing it.
+    commit_latency: LatencyHistogram,
+    live: Option<LiveCounters>,
+    /// Whether the scanned blocks cover the whole run, which the comparison with the live
+    /// counters below requires. Transactions committed before `since` would otherwise
+    /// count as missing.
+    covers_run: Option<bool>,
+    /// Sent transactions found in no block.
+    missing: Option<usize>,
+    /// Found outcomes minus the ones counted live, positive if the live counters missed some.
+    committed_difference: Option<i64>,
+    rejected_difference: Option<i64>,
+    unresolved: Option<Resolution>,
+}
+
+/// Scans the blocks committed since the given time, or all blocks, for the transactions
+/// tagged with the run id. If the live counters are given, the report says how they compare.
+///
+/// # Errors
+/// if the blocks can't be queried.
+pub async fn reconcile(
+    client: &Client,
+    run_id: RunId,
+    since: Option<DateTime<Utc>>,
+    live: Option<LiveCounters>,
+) -> Result<ReconcileReport> {
+    let scan_since = since.map(|since| since - chrono::Duration::seconds(CLOCK_SKEW_SECS));
+    let (blocks_scanned, transactions) = client
+        .find_run_transactions_since(scan_since, &run_id)
+        .await?;
+    Ok(make_report(
+        run_id,
+        since,
+        blocks_scanned,
+        transactions,
+        live,
+    ))
+}
+
+/// Summarizes the found transactions of the run and compares them with the live counters,
+/// as long as the scan started no later than the run.
+fn make_report(
+    run_id: RunId,
+    since: Option<DateTime<Utc>>,
+    blocks_scanned: usize,
+    transactions: Vec<RunTransaction>,
+    live: Option<LiveCounters>,
+) -> ReconcileReport {
+    let mut report = ReconcileReport {
+        run_id,
+        since,
+        blocks_scanned,
+        found: Outcomes::default(),
+        operations: BTreeMap::new(),
+        commit_latency: LatencyHistogram::default(),
+        live: None,
+        covers_run: None,
+        missing: None,
+        committed_difference: None,
+        rejected_difference: None,
+        unresolved: None,
+    };
+    let mut outcomes = HashMap::with_capacity(transactions.len());
+    for transaction in transactions {
+        report.found.count(transaction.is_rejected);
+        report
+            .operations
+            .entry(
+                transaction
+                    .operation
+                    .unwrap_or_else(|| "Unknown".to_owned()),
+            )
+            .or_default()
+            .count(transaction.is_rejected);
+        if let (false, Some(sent_at_ms)) = (transaction.is_rejected, transaction.sent_at_ms) {
+            report.commit_latency.record(Duration::from_millis(
+                transaction.committed_at_ms.saturating_sub(sent_at_ms),
+            ));
+        }
+        outcomes.insert(transaction.hash, transaction.is_rejected);
+    }
+    if let Some(live) = live {
+        let covers_run = !matches!(since, Some(since) if since > live.started_at);
+        report.covers_run = Some(covers_run);
+        if covers_run {
+            let difference = |found: usize, counted: usize| found as i64 - counted as i64;
+            report.missing = Some(live.txs_sent.saturating_sub(outcomes.len()));
+            report.committed_difference =
+                Some(difference(report.found.committed, live.txs_committed));
+            report.rejected_difference = Some(difference(report.found.rejected, live.txs_rejected));
+            let mut resolution = Resolution::default();
+            for hash in &live.unresolved {
+                match outcomes.get(hash) {
+                    Some(false) => resolution.committed.push(*hash),
+                    Some(true) => resolution.rejected.push(*hash),
+                    None => resolution.missing.push(*hash),
+                }
+            }
+            report.unresolved = Some(resolution);
+        }
+        report.live = Some(live);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> Hash {
+        Hash::new([byte])
+    }
+
+    fn time(at: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(at)
+            .expect("Valid time")
+            .with_timezone(&Utc)
+    }
+
+    fn transaction(byte: u8, is_rejected: bool) -> RunTransaction {
+        RunTransaction {
+            hash: hash(byte),
+            is_rejected,
+            operation: Some("RegisterDomain".to_owned()),
+            sent_at_ms: Some(1_000),
+            committed_at_ms: 1_250,
+        }
+    }
+
+    fn live() -> LiveCounters {
+        LiveCounters {
+            started_at: time("2024-01-01T00:00:00Z"),
+            txs_sent: 4,
+            txs_committed: 1,
+            txs_rejected: 1,
+            txs_timed_out: 2,
+            txs_in_flight: 0,
+            unresolved: vec![hash(3), hash(4)],
+        }
+    }
+
+    fn report(since: &str) -> ReconcileReport {
+        make_report(
+            "run".parse().expect("Valid run id"),
+            Some(time(since)),
+            2,
+            vec![
+                transaction(1, false),
+                transaction(2, true),
+                transaction(3, false),
+            ],
+            Some(live()),
+        )
+    }
+
+    #[test]
+    fn compares_with_live_counters_when_the_whole_run_is_scanned() {
+        let report = report("2024-01-01T00:00:00Z");
+        assert_eq!(report.covers_run, Some(true));
+        assert_eq!(report.found.committed, 2);
+        assert_eq!(report.found.rejected, 1);
+        assert_eq!(report.operations["RegisterDomain"].committed, 2);
+        assert_eq!(report.missing, Some(1));
+        assert_eq!(report.committed_difference, Some(1));
+        assert_eq!(report.rejected_difference, Some(0));
+        let unresolved = report
+            .unresolved
+            .expect("Resolution of timed out transactions");
+        assert_eq!(unresolved.committed, [hash(3)]);
+        assert!(unresolved.rejected.is_empty());
+        assert_eq!(unresolved.missing, [hash(4)]);
+        assert_eq!(report.commit_latency.count(), 2);
+    }
+
+    #[test]
+    fn does_not_compare_a_partial_scan() {
+        let report = report("2024-01-01T01:00:00Z");
+        assert_eq!(report.covers_run, Some(false));
+        assert_eq!(report.found.committed, 2);
+        assert_eq!(report.missing, None);
+        assert_eq!(report.committed_difference, None);
+        assert_eq!(report.rejected_difference, None);
+        assert!(report.unresolved.is_none());
+    }
+}
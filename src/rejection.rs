@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use iroha_crypto::Hash;
+use iroha_data_model::{
+    events::pipeline::PipelineRejectionReason, isi::InstructionExpr,
+    transaction::error::TransactionRejectionReason,
+};
+use serde::{Deserialize, Serialize};
+
+/// Rejection of a sent transaction kept in the status for diagnostics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rejection {
+    pub at: DateTime<Utc>,
+    pub hash: Hash,
+    pub category: String,
+    pub message: String,
+}
+
+impl Rejection {
+    pub fn new(hash: Hash, reason: &PipelineRejectionReason) -> Self {
+        Self {
+            at: Utc::now(),
+            hash,
+            category: category(reason),
+            message: reason.to_string(),
+        }
+    }
+}
+
+/// Returns the category rejections are grouped by in the status. Instruction execution
+/// failures are told apart by the kind of the failed instruction, e.g.
+/// `instruction_execution:Register`.
+pub fn category(reason: &PipelineRejectionReason) -> String {
+    let reason = match reason {
+        PipelineRejectionReason::Block(_) => return "block".to_owned(),
+        PipelineRejectionReason::Transaction(reason) => reason,
+    };
+    let category = match reason {
+        TransactionRejectionReason::UnexpectedGenesisAccountSignature => "signature_check",
+        TransactionRejectionReason::AccountDoesNotExist(_) => "account_does_not_exist",
+        TransactionRejectionReason::LimitCheck(_) => "limits_exceeded",
+        TransactionRejectionReason::Validation(_) => "validation",
+        TransactionRejectionReason::InstructionExecution(fail) => {
+            return format!(
+                "instruction_execution:{}",
+                instruction_kind(&fail.instruction)
+            );
+        }
+        TransactionRejectionReason::WasmExecution(_) => "wasm_execution",
+        TransactionRejectionReason::Expired(_) => "expired",
+    };
+    category.to_owned()
+}
+
+/// Returns the name of the instruction variant, or `Other` for the ones the load never sends.
+fn instruction_kind(instruction: &InstructionExpr) -> &'static str {
+    match instruction {
+        InstructionExpr::Register(_) => "Register",
+        InstructionExpr::Unregister(_) => "Unregister",
+        InstructionExpr::Mint(_) => "Mint",
+        InstructionExpr::Burn(_) => "Burn",
+        InstructionExpr::Transfer(_) => "Transfer",
+        InstructionExpr::SetKeyValue(_) => "SetKeyValue",
+        InstructionExpr::RemoveKeyValue(_) => "RemoveKeyValue",
+        InstructionExpr::Grant(_) => "Grant",
+        InstructionExpr::Revoke(_) => "Revoke",
+        InstructionExpr::ExecuteTrigger(_) => "ExecuteTrigger",
+        _ => "Other",
+    }
+}
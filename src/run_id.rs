@@ -0,0 +1,165 @@
+use color_eyre::eyre::{bail, Report, Result};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Length of generated run ids.
+const GENERATED_LEN: usize = 8;
+/// Longest accepted run id, so names of entities stay well within the limits of Iroha.
+const MAX_LEN: usize = 32;
+
+/// Keys of the metadata attributing transactions to the run and the operation they perform.
+pub const METADATA_RUN_ID: &str = "load_run_id";
+pub const METADATA_OPERATION: &str = "load_operation";
+pub const METADATA_SEQUENCE: &str = "load_sequence";
+pub const METADATA_SENT_AT_MS: &str = "load_sent_at_ms";
+
+/// Prefixes and suffixes around the index in base names of the entities registered by
+/// operations, see `make_instruction_by_operation`.
+const ENTITY_NAMES: [(&str, &str); 10] = [
+    ("alice", ""),
+    ("wonderland", ""),
+    ("rose_quantity", ""),
+    ("rose_big_quantity", ""),
+    ("rose_fixed", ""),
+    ("rose_store", ""),
+    ("rose", "_to_transfer"),
+    ("bob", "_to_transfer"),
+    ("rose", "_to_mint"),
+    ("bob", "_to_mint"),
+];
+
+/// Identifier of a run which ends the names of all entities it registers, so runs
+/// against the same network do not collide and their entities can be told apart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RunId(String);
+
+impl RunId {
+    pub fn generate() -> Self {
+        let id = rand::thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(GENERATED_LEN)
+            .map(|byte| char::from(byte).to_ascii_lowercase())
+            .collect();
+        Self(id)
+    }
+
+    /// Returns the name of an entity of this run made from its base name.
+    pub fn name(&self, base: impl fmt::Display) -> String {
+        format!("{}_{}", base, self.0)
+    }
+
+    /// Tells whether the entity name was made by an operation of this run: one of the
+    /// known base names with an index, followed by `_` and the id. Entities of other
+    /// runs whose ids end with this one, e.g. `a_b` for `b`, are not matched.
+    pub fn owns(&self, name: &str) -> bool {
+        let base = match name
+            .strip_suffix(self.0.as_str())
+            .and_then(|name| name.strip_suffix('_'))
+        {
+            Some(base) => base,
+            None => return false,
+        };
+        ENTITY_NAMES.iter().any(|(prefix, suffix)| {
+            matches!(
+                base.strip_prefix(prefix).and_then(|base| base.strip_suffix(suffix)),
+                Some(index) if !index.is_empty() && index.chars().all(|c| c.is_ascii_digit())
+            )
+        })
+    }
+}
+
+/// Accepts non-empty ids of at most 32 ASCII letters, digits, `-` and `_`, which are valid
+/// in entity names.
+impl FromStr for RunId {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty()
+            || !s
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!(
+                "Run id `{}` has to consist of ASCII letters, digits, `-` and `_`",
+                s
+            );
+        }
+        if s.len() > MAX_LEN {
+            bail!("Run id `{}` is longer than {} characters", s, MAX_LEN);
+        }
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl TryFrom<String> for RunId {
+    type Error = Report;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<RunId> for String {
+    fn from(run_id: RunId) -> Self {
+        run_id.0
+    }
+}
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_id(s: &str) -> RunId {
+        s.parse().expect("Valid run id")
+    }
+
+    #[test]
+    fn owns_names_made_by_its_operations() {
+        let run = run_id("f2dsrzqq");
+        for name in [
+            "alice42_f2dsrzqq",
+            "wonderland0_f2dsrzqq",
+            "rose_big_quantity7_f2dsrzqq",
+            "rose3_to_transfer_f2dsrzqq",
+            "bob3_to_mint_f2dsrzqq",
+        ] {
+            assert!(run.owns(name), "{}", name);
+        }
+        assert!(run.owns(&run.name(format!("rose_store{}", 5))));
+    }
+
+    #[test]
+    fn does_not_own_other_names() {
+        let run = run_id("b");
+        for name in [
+            "alice1_a_b",
+            "wonderland_b",
+            "alice_b",
+            "carol1_b",
+            "alice1b",
+            "alice1_to_mint_b",
+            "alice1_c",
+            "b",
+        ] {
+            assert!(!run.owns(name), "{}", name);
+        }
+        assert!(run_id("a_b").owns("alice1_a_b"));
+    }
+
+    #[test]
+    fn rejects_invalid_ids() {
+        let longest = "a".repeat(MAX_LEN);
+        assert_eq!(run_id(&longest).to_string(), longest);
+        for id in ["", "a b", "ünicode", &"a".repeat(MAX_LEN + 1)] {
+            assert!(id.parse::<RunId>().is_err(), "{}", id);
+        }
+    }
+}
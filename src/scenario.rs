@@ -0,0 +1,296 @@
+use crate::{
+    arrival::Arrival,
+    control::{LoadSettings, OperationSettings},
+    load_shape::LoadShape,
+    number::{default_weight, PositiveFloat},
+    operation::Operation,
+};
+use color_eyre::eyre::{bail, Result, WrapErr as _};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::{
+    select,
+    sync::Notify,
+    time::{self, Instant},
+};
+use tracing::info;
+
+/// How often the stage completion is checked.
+const STAGE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Load profile consisting of consecutive stages.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub stages: Vec<Stage>,
+    /// Distribution of intervals between transactions during all stages.
+    #[serde(default)]
+    pub arrival: Arrival,
+    /// Seed of the generator driving the load, random if absent.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// What to do after the last stage is over.
+    #[serde(default)]
+    pub tail: Tail,
+}
+
+/// Stage of a scenario. It is over when its duration elapses or when all its
+/// operations have run out of their counts, whichever comes first.
+///
+/// The rate is either given for the whole stage with `tps` or `shape` and split
+/// between operations by their `weight`, or given for every operation with its own `tps`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stage {
+    pub name: String,
+    #[serde(default)]
+    pub duration_secs: Option<PositiveFloat>,
+    #[serde(default)]
+    pub tps: Option<PositiveFloat>,
+    #[serde(default)]
+    pub shape: Option<LoadShape>,
+    #[serde(default)]
+    pub operations: BTreeMap<Operation, OperationSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperationSpec {
+    #[serde(default)]
+    pub weight: Option<PositiveFloat>,
+    #[serde(default)]
+    pub tps: Option<PositiveFloat>,
+    /// Amount of transactions to send, unlimited if absent.
+    #[serde(default)]
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum Tail {
+    /// Keep submitting empty transactions at the rate of the last stage.
+    #[default]
+    EmptyTransactions,
+    /// Stop sending transactions but keep serving the status.
+    Idle,
+    /// Shut the daemon down.
+    Stop,
+}
+
+impl Scenario {
+    /// Reads and validates the scenario file.
+    ///
+    /// # Errors
+    /// if the file can't be read or describes an invalid scenario.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Failed to open the scenario file `{}`", path.display()))?;
+        let scenario: Self = serde_json::from_reader(file)
+            .wrap_err_with(|| format!("Failed to parse the scenario file `{}`", path.display()))?;
+        if scenario.stages.is_empty() {
+            bail!("Scenario has to contain at least one stage");
+        }
+        for stage in &scenario.stages {
+            stage
+                .load()
+                .wrap_err_with(|| format!("Invalid stage `{}`", stage.name))?;
+        }
+        Ok(scenario)
+    }
+}
+
+impl Stage {
+    /// Resolves the rate of the stage and settings of its operations. The rate is `None`
+    /// if it is given for every operation, which is its weight then.
+    ///
+    /// # Errors
+    /// if rates are given inconsistently or the stage would never end.
+    pub fn load(&self) -> Result<(Option<LoadShape>, BTreeMap<Operation, OperationSettings>)> {
+        if self.duration_secs.is_none()
+            && (self.operations.is_empty() || self.operations.values().any(|op| op.count.is_none()))
+        {
+            bail!("Stage without a duration has to give a count to every operation");
+        }
+        let stage_shape = match (self.tps, self.shape) {
+            (Some(_), Some(_)) => bail!("Stage `tps` can't be used together with `shape`"),
+            (Some(tps), None) => Some(LoadShape::from(tps)),
+            (None, shape) => shape,
+        };
+        let shape = match stage_shape {
+            Some(shape) => {
+                shape.validate()?;
+                if self.operations.values().any(|op| op.tps.is_some()) {
+                    bail!("Operation `tps` can't be used together with the stage rate");
+                }
+                Some(shape)
+            }
+            None => {
+                if self.operations.values().any(|op| op.weight.is_some()) {
+                    bail!("Operation `weight` requires the stage rate");
+                }
+                if self.operations.is_empty() || self.operations.values().any(|op| op.tps.is_none())
+                {
+                    bail!("Either the stage or every operation needs `tps`");
+                }
+                None
+            }
+        };
+        let operations = self
+            .operations
+            .iter()
+            .map(|(op, spec)| {
+                let settings = OperationSettings {
+                    weight: spec.weight.or(spec.tps).unwrap_or_else(default_weight),
+                    count: spec.count,
+                };
+                (*op, settings)
+            })
+            .collect();
+        Ok((shape, operations))
+    }
+}
+
+/// Applies stages of the scenario to the load settings one after another. The settings
+/// are expected to be paused, so nothing is sent before the first stage starts.
+pub async fn drive_scenario(
+    scenario: Scenario,
+    settings: Arc<RwLock<LoadSettings>>,
+    notify_close: Arc<Notify>,
+) {
+    let closed = notify_close.notified();
+    tokio::pin!(closed);
+    info!(scenario = ?scenario.name, "Starting the scenario");
+    for (index, stage) in scenario.stages.into_iter().enumerate() {
+        let (shape, operations) = stage.load().expect("Stages are validated on load");
+        info!(stage = %stage.name, shape = ?shape, "Starting the stage");
+        // A stage without operations only sends empty transactions until its deadline
+        let has_operations = !operations.is_empty();
+        let deadline = stage
+            .duration_secs
+            .map(|duration| Instant::now() + Duration::from_secs_f64(duration.into()));
+        {
+            let mut settings = settings.write().expect("Failed to lock to start the stage");
+            settings.start_stage(stage.name, shape, operations);
+            // The load is paused until the first stage starts
+            if index == 0 {
+                settings.resume();
+            }
+        }
+        loop {
+            select! {
+                _ = time::sleep(STAGE_CHECK_INTERVAL) => {},
+                _ = &mut closed => return,
+            }
+            let is_exhausted = settings
+                .read()
+                .expect("Failed to lock to check the stage")
+                .is_exhausted();
+            if (has_operations && is_exhausted)
+                || matches!(deadline, Some(deadline) if Instant::now() >= deadline)
+            {
+                break;
+            }
+        }
+    }
+    info!(tail = ?scenario.tail, "All stages are over");
+    match scenario.tail {
+        Tail::EmptyTransactions => settings
+            .write()
+            .expect("Failed to lock to finish stages")
+            .finish_stages(),
+        Tail::Idle => {
+            let mut settings = settings.write().expect("Failed to lock to finish stages");
+            settings.finish_stages();
+            settings.pause();
+        }
+        Tail::Stop => notify_close.notify_waiters(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(json: &str) -> Stage {
+        serde_json::from_str(json).expect("Valid stage")
+    }
+
+    #[test]
+    fn stage_rate_is_split_by_weights() {
+        let (shape, operations) = stage(
+            r#"{"name": "s", "duration_secs": 60, "tps": 10,
+                "operations": {"RegisterDomain": {"weight": 3}, "RegisterAccount": {}}}"#,
+        )
+        .load()
+        .expect("Valid stage");
+        assert!(matches!(shape, Some(LoadShape::Constant { tps }) if f64::from(tps) == 10.0));
+        assert_eq!(
+            f64::from(operations[&Operation::RegisterDomain].weight),
+            3.0
+        );
+        assert_eq!(
+            f64::from(operations[&Operation::RegisterAccount].weight),
+            1.0
+        );
+    }
+
+    #[test]
+    fn operation_rates_become_weights() {
+        let (shape, operations) = stage(
+            r#"{"name": "s", "operations": {
+                "RegisterDomain": {"tps": 3, "count": 10},
+                "RegisterAccount": {"tps": 1, "count": 5}}}"#,
+        )
+        .load()
+        .expect("Valid stage");
+        assert!(shape.is_none());
+        assert_eq!(
+            f64::from(operations[&Operation::RegisterDomain].weight),
+            3.0
+        );
+        assert_eq!(operations[&Operation::RegisterAccount].count, Some(5));
+    }
+
+    #[test]
+    fn stage_without_operations_needs_a_rate() {
+        assert!(stage(r#"{"name": "s", "duration_secs": 60, "tps": 5}"#)
+            .load()
+            .is_ok());
+        assert!(stage(r#"{"name": "s", "duration_secs": 60}"#)
+            .load()
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_inconsistent_stages() {
+        for json in [
+            // Never ends
+            r#"{"name": "s", "tps": 5}"#,
+            r#"{"name": "s", "tps": 5, "operations": {"RegisterDomain": {}}}"#,
+            // Both stage rates
+            r#"{"name": "s", "duration_secs": 60, "tps": 5,
+                "shape": {"kind": "Constant", "tps": 5}}"#,
+            // Operation rate together with the stage rate
+            r#"{"name": "s", "duration_secs": 60, "tps": 5,
+                "operations": {"RegisterDomain": {"tps": 1}}}"#,
+            // Weight without the stage rate
+            r#"{"name": "s", "duration_secs": 60,
+                "operations": {"RegisterDomain": {"weight": 1, "tps": 1}}}"#,
+            // Not every operation has a rate
+            r#"{"name": "s", "duration_secs": 60,
+                "operations": {"RegisterDomain": {"tps": 1}, "RegisterAccount": {}}}"#,
+            // Invalid shape
+            r#"{"name": "s", "duration_secs": 60,
+                "shape": {"kind": "Sine", "mean": 1, "amplitude": 2, "period_secs": 60}}"#,
+        ] {
+            assert!(stage(json).load().is_err(), "{}", json);
+        }
+    }
+}
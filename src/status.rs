@@ -1,34 +1,742 @@
+use crate::{
+    history::Snapshot,
+    latency::LatencyHistogram,
+    metrics::{Encoder, MetricKind},
+    operation::Operation,
+    rate::RateMeter,
+    reconcile::LiveCounters,
+    rejection::Rejection,
+    run_id::RunId,
+};
 use chrono::prelude::*;
-use serde::{Deserialize, Serialize};
+use iroha_crypto::Hash;
+use iroha_data_model::events::pipeline::PipelineRejectionReason;
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::{Duration, Instant},
+};
 
+/// Amount of timed out transaction hashes kept in the status.
+const TIMED_OUT_HASHES: usize = 1000;
+/// Amount of the latest rejections of sent transactions kept in the status.
+const RECENT_REJECTIONS: usize = 100;
+
+/// Counters of the load. Transactions that were not sent by the script are counted
+/// separately from its own ones.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Status {
+    /// Run whose transactions are counted.
+    run_id: Option<RunId>,
+    #[serde(flatten)]
+    uptime: Uptime,
     txs_committed: usize,
     txs_rejected: usize,
     txs_sent: usize,
     txs_unknown: usize,
+    /// Transactions which failed to be submitted.
+    txs_failed: usize,
+    /// Transactions committed by the network which were not sent by this script.
+    txs_foreign_committed: usize,
+    /// Transactions rejected by the network which were not sent by this script.
+    txs_foreign_rejected: usize,
+    /// Rejected sent transactions by the category of the rejection reason.
+    rejection_reasons: BTreeMap<String, usize>,
+    /// The latest rejections of sent transactions with full reasons.
+    recent_rejections: VecDeque<Rejection>,
+    /// Sent transactions which got no pipeline outcome in time.
+    txs_timed_out: usize,
+    /// Hashes of the most recent timed out transactions.
+    timed_out: VecDeque<Hash>,
+    /// Outcomes of sent transactions recovered from blocks after the event stream was lost.
+    txs_reconciled: usize,
+    blocks_committed: usize,
+    blocks_rejected: usize,
+    /// Times the event stream was lost.
+    event_stream_disconnects: usize,
+    /// Total time without the event stream in seconds.
+    event_stream_downtime_secs: f64,
     latest_committed_transaction: Option<DateTime<Utc>>,
     latest_rejected_transaction: Option<DateTime<Utc>>,
     latest_sent_at: Option<DateTime<Utc>>,
+    commit_latency: LatencyHistogram,
+    rejection_latency: LatencyHistogram,
+    /// How late sends were relative to their schedule.
+    send_lateness: LatencyHistogram,
+    /// Achieved rate of sent transactions.
+    #[serde(skip_deserializing)]
+    sent_tps: RateMeter,
+    /// Rate of commits of sent transactions.
+    #[serde(skip_deserializing)]
+    committed_tps: RateMeter,
+    /// Rate of rejections of sent transactions.
+    #[serde(skip_deserializing)]
+    rejected_tps: RateMeter,
+    /// Counters of every peer transactions were submitted to, by its Torii URL.
+    peers: BTreeMap<String, TxCounters>,
+    /// Counters of every performed operation.
+    operations: BTreeMap<Operation, TxCounters>,
+    /// Counters of empty transactions sent once the operations are exhausted.
+    empty_transactions: TxCounters,
+    /// Sent transactions that are waiting for their pipeline event.
+    #[serde(skip)]
+    in_flight: HashMap<Hash, InFlight>,
+    /// Set while outcomes missed by the event stream are yet to be reconciled,
+    /// so sent transactions are not timed out in the meantime.
+    #[serde(skip)]
+    is_expiry_paused: bool,
+}
+
+/// Start time of the run, serialized together with the time elapsed since then.
+#[derive(Debug, Clone, Deserialize)]
+struct Uptime {
+    started_at: DateTime<Utc>,
+}
+
+impl Default for Uptime {
+    fn default() -> Self {
+        Self {
+            started_at: Utc::now(),
+        }
+    }
+}
+
+impl Uptime {
+    fn secs(&self) -> i64 {
+        (Utc::now() - self.started_at).num_seconds()
+    }
+}
+
+impl Serialize for Uptime {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("started_at", &self.started_at)?;
+        map.serialize_entry("uptime_secs", &self.secs())?;
+        map.end()
+    }
+}
+
+/// Counters of a part of the sent transactions, e.g. the ones sent to a peer.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TxCounters {
+    txs_sent: usize,
+    txs_failed: usize,
+    txs_committed: usize,
+    txs_rejected: usize,
+    txs_timed_out: usize,
+    commit_latency: LatencyHistogram,
+}
+
+/// Sent transaction waiting for its outcome. It is saved to the state file without
+/// the time of the submission, which can't survive a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InFlight {
+    #[serde(skip, default = "Instant::now")]
+    submitted_at: Instant,
+    peer: String,
+    /// `None` for empty transactions.
+    operation: Option<Operation>,
+    /// Second the send is counted in by the sent rate.
+    #[serde(skip)]
+    sent_second: i64,
 }
 
 impl Status {
-    pub fn tx_is_sent(&mut self) -> Option<DateTime<Utc>> {
+    pub fn for_run(run_id: RunId) -> Self {
+        Self {
+            run_id: Some(run_id),
+            ..Self::default()
+        }
+    }
+
+    pub fn run_id(&self) -> Option<&RunId> {
+        self.run_id.as_ref()
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.uptime.started_at
+    }
+
+    pub fn tx_is_sent(
+        &mut self,
+        hash: Hash,
+        submitted_at: Instant,
+        peer: &str,
+        operation: Option<Operation>,
+    ) -> Option<DateTime<Utc>> {
+        for counters in self.counters_mut(peer, operation) {
+            counters.txs_sent += 1;
+        }
+        let sent_second = self.sent_tps.record();
+        self.in_flight.insert(
+            hash,
+            InFlight {
+                submitted_at,
+                peer: peer.to_owned(),
+                operation,
+                sent_second,
+            },
+        );
         self.txs_sent += 1;
         self.latest_sent_at.replace(Utc::now())
     }
 
-    pub fn tx_is_committed(&mut self) -> Option<DateTime<Utc>> {
-        self.txs_committed += 1;
-        self.latest_committed_transaction.replace(Utc::now())
+    /// Takes back a transaction registered by [`Self::tx_is_sent`] before its submission,
+    /// once the submission has failed. Returns `false` if it was not registered.
+    pub fn tx_is_not_sent(&mut self, hash: &Hash) -> bool {
+        let InFlight {
+            peer,
+            operation,
+            sent_second,
+            ..
+        } = match self.in_flight.remove(hash) {
+            Some(in_flight) => in_flight,
+            None => return false,
+        };
+        for counters in self.counters_mut(&peer, operation) {
+            counters.txs_sent = counters.txs_sent.saturating_sub(1);
+        }
+        self.sent_tps.unrecord(sent_second);
+        self.txs_sent = self.txs_sent.saturating_sub(1);
+        true
+    }
+
+    /// Credits a commit of the transaction if it was sent by this script,
+    /// otherwise counts it as a foreign one. Late commits of timed out transactions are ignored.
+    pub fn tx_is_committed(&mut self, hash: &Hash) -> Option<DateTime<Utc>> {
+        match self.in_flight.remove(hash) {
+            Some(InFlight {
+                submitted_at,
+                peer,
+                operation,
+                ..
+            }) => {
+                let latency = submitted_at.elapsed();
+                for counters in self.counters_mut(&peer, operation) {
+                    counters.commit_latency.record(latency);
+                    counters.txs_committed += 1;
+                }
+                self.commit_latency.record(latency);
+                self.committed_tps.record();
+                self.txs_committed += 1;
+                self.latest_committed_transaction.replace(Utc::now())
+            }
+            None if self.timed_out.contains(hash) => None,
+            None => {
+                self.txs_foreign_committed += 1;
+                None
+            }
+        }
+    }
+
+    /// Credits a rejection of the transaction if it was sent by this script,
+    /// otherwise counts it as a foreign one. Late rejections of timed out transactions are ignored.
+    pub fn tx_is_rejected(
+        &mut self,
+        hash: &Hash,
+        reason: &PipelineRejectionReason,
+    ) -> Option<DateTime<Utc>> {
+        match self.in_flight.remove(hash) {
+            Some(InFlight {
+                submitted_at,
+                peer,
+                operation,
+                ..
+            }) => {
+                self.rejection_is_known(*hash, reason);
+                for counters in self.counters_mut(&peer, operation) {
+                    counters.txs_rejected += 1;
+                }
+                self.rejection_latency.record(submitted_at.elapsed());
+                self.rejected_tps.record();
+                self.txs_rejected += 1;
+                self.latest_rejected_transaction.replace(Utc::now())
+            }
+            None if self.timed_out.contains(hash) => None,
+            None => {
+                self.txs_foreign_rejected += 1;
+                None
+            }
+        }
+    }
+
+    /// Gives up waiting for the outcome of the sent transaction.
+    pub fn tx_is_timed_out(&mut self, hash: &Hash) {
+        let InFlight {
+            peer, operation, ..
+        } = match self.in_flight.remove(hash) {
+            Some(in_flight) => in_flight,
+            None => return,
+        };
+        for counters in self.counters_mut(&peer, operation) {
+            counters.txs_timed_out += 1;
+        }
+        self.txs_timed_out += 1;
+        if self.timed_out.len() == TIMED_OUT_HASHES {
+            self.timed_out.pop_front();
+        }
+        self.timed_out.push_back(*hash);
+    }
+
+    /// Returns the sent transactions waiting for their outcome, to be saved to the state file.
+    pub fn in_flight(&self) -> Vec<(Hash, InFlight)> {
+        self.in_flight
+            .iter()
+            .map(|(hash, in_flight)| (*hash, in_flight.clone()))
+            .collect()
+    }
+
+    /// Counts transactions which were in flight when the resumed run stopped as timed out,
+    /// since their outcomes may have been missed while the daemon was down.
+    pub fn in_flight_is_lost(&mut self, in_flight: Vec<(Hash, InFlight)>) {
+        for (hash, in_flight) in in_flight {
+            self.in_flight.insert(hash, in_flight);
+            self.tx_is_timed_out(&hash);
+        }
+    }
+
+    /// Times out sent transactions which have been waiting for their outcome for `timeout`,
+    /// unless the expiry is paused.
+    pub fn expire_in_flight(&mut self, timeout: Duration) {
+        if self.is_expiry_paused {
+            return;
+        }
+        let expired = self
+            .in_flight
+            .iter()
+            .filter(|(_, in_flight)| in_flight.submitted_at.elapsed() >= timeout)
+            .map(|(hash, _)| *hash)
+            .collect::<Vec<_>>();
+        for hash in &expired {
+            self.tx_is_timed_out(hash);
+        }
+    }
+
+    /// Credits the outcome of the sent transaction found in a block, without a latency
+    /// since the time of the outcome is unknown.
+    pub fn tx_is_reconciled(&mut self, hash: &Hash, rejection: Option<&PipelineRejectionReason>) {
+        let InFlight {
+            peer, operation, ..
+        } = match self.in_flight.remove(hash) {
+            Some(in_flight) => in_flight,
+            None => return,
+        };
+        self.txs_reconciled += 1;
+        for counters in self.counters_mut(&peer, operation) {
+            if rejection.is_some() {
+                counters.txs_rejected += 1;
+            } else {
+                counters.txs_committed += 1;
+            }
+        }
+        match rejection {
+            Some(reason) => {
+                self.rejection_is_known(*hash, reason);
+                self.rejected_tps.record();
+                self.txs_rejected += 1;
+            }
+            None => {
+                self.committed_tps.record();
+                self.txs_committed += 1;
+            }
+        }
+    }
+
+    /// Counts the reason of a rejection of a sent transaction and keeps it among the recent ones.
+    fn rejection_is_known(&mut self, hash: Hash, reason: &PipelineRejectionReason) {
+        let rejection = Rejection::new(hash, reason);
+        *self
+            .rejection_reasons
+            .entry(rejection.category.clone())
+            .or_default() += 1;
+        if self.recent_rejections.len() == RECENT_REJECTIONS {
+            self.recent_rejections.pop_front();
+        }
+        self.recent_rejections.push_back(rejection);
+    }
+
+    pub fn block_is_committed(&mut self) {
+        self.blocks_committed += 1;
+    }
+
+    pub fn block_is_rejected(&mut self) {
+        self.blocks_rejected += 1;
     }
 
-    pub fn tx_is_rejected(&mut self) -> Option<DateTime<Utc>> {
-        self.txs_rejected += 1;
-        self.latest_rejected_transaction.replace(Utc::now())
+    pub fn event_stream_is_lost(&mut self) {
+        self.event_stream_disconnects += 1;
+    }
+
+    pub fn event_stream_is_restored(&mut self, downtime: Duration) {
+        self.event_stream_downtime_secs += downtime.as_secs_f64();
+    }
+
+    /// Stops timing out sent transactions until [`Self::resume_expiry`], e.g. while
+    /// their outcomes can only be found in blocks later.
+    pub fn pause_expiry(&mut self) {
+        self.is_expiry_paused = true;
+    }
+
+    pub fn resume_expiry(&mut self) {
+        self.is_expiry_paused = false;
     }
 
     pub fn tx_is_unknown(&mut self) {
         self.txs_unknown += 1;
     }
+
+    pub fn tx_submit_failed(&mut self, operation: Option<Operation>) {
+        self.operation_mut(operation).txs_failed += 1;
+        self.txs_failed += 1;
+    }
+
+    /// Counts a failed submission to the peer, the transaction itself is counted
+    /// by [`Self::tx_submit_failed`].
+    pub fn peer_submit_failed(&mut self, peer: &str) {
+        self.peers.entry(peer.to_owned()).or_default().txs_failed += 1;
+    }
+
+    fn operation_mut(&mut self, operation: Option<Operation>) -> &mut TxCounters {
+        match operation {
+            Some(operation) => self.operations.entry(operation).or_default(),
+            None => &mut self.empty_transactions,
+        }
+    }
+
+    /// Counters of the peer and the operation a transaction belongs to.
+    fn counters_mut(&mut self, peer: &str, operation: Option<Operation>) -> [&mut TxCounters; 2] {
+        let peer = self.peers.entry(peer.to_owned()).or_default();
+        let operation = match operation {
+            Some(operation) => self.operations.entry(operation).or_default(),
+            None => &mut self.empty_transactions,
+        };
+        [peer, operation]
+    }
+
+    pub fn send_is_late(&mut self, lateness: Duration) {
+        self.send_lateness.record(lateness);
+    }
+
+    /// Summarizes the status for the history, with rates over the last minute.
+    pub fn snapshot(&self, target_tps: f64) -> Snapshot {
+        let latency_ms = |quantile| {
+            self.commit_latency
+                .quantile(quantile)
+                .map(|latency| latency.as_secs_f64() * 1000.0)
+        };
+        Snapshot {
+            at: Utc::now(),
+            uptime_secs: self.uptime.secs(),
+            txs_sent: self.txs_sent,
+            txs_committed: self.txs_committed,
+            txs_rejected: self.txs_rejected,
+            txs_failed: self.txs_failed,
+            txs_timed_out: self.txs_timed_out,
+            txs_in_flight: self.in_flight.len(),
+            sent_tps: self.sent_tps.rate(60),
+            committed_tps: self.committed_tps.rate(60),
+            rejected_tps: self.rejected_tps.rate(60),
+            target_tps,
+            commit_latency_p50_ms: latency_ms(0.5),
+            commit_latency_p99_ms: latency_ms(0.99),
+        }
+    }
+
+    /// Counters to reconcile with the blocks, with the timed out and in-flight transactions
+    /// as the unresolved ones.
+    pub fn live_counters(&self) -> LiveCounters {
+        LiveCounters {
+            started_at: self.uptime.started_at,
+            txs_sent: self.txs_sent,
+            txs_committed: self.txs_committed,
+            txs_rejected: self.txs_rejected,
+            txs_timed_out: self.txs_timed_out,
+            txs_in_flight: self.in_flight.len(),
+            unresolved: self
+                .timed_out
+                .iter()
+                .chain(self.in_flight.keys())
+                .copied()
+                .collect(),
+        }
+    }
+
+    pub fn encode_metrics(&self, encoder: &mut Encoder) {
+        let counters = [
+            (
+                "txs_sent_total",
+                "Transactions sent by the script.",
+                self.txs_sent,
+            ),
+            (
+                "txs_committed_total",
+                "Sent transactions that were committed.",
+                self.txs_committed,
+            ),
+            (
+                "txs_rejected_total",
+                "Sent transactions that were rejected.",
+                self.txs_rejected,
+            ),
+            (
+                "txs_unknown_total",
+                "Events with an unknown status.",
+                self.txs_unknown,
+            ),
+            (
+                "txs_failed_total",
+                "Transactions which failed to be submitted.",
+                self.txs_failed,
+            ),
+            (
+                "txs_foreign_committed_total",
+                "Committed transactions that were not sent by the script.",
+                self.txs_foreign_committed,
+            ),
+            (
+                "txs_foreign_rejected_total",
+                "Rejected transactions that were not sent by the script.",
+                self.txs_foreign_rejected,
+            ),
+            (
+                "txs_timed_out_total",
+                "Sent transactions which got no outcome in time.",
+                self.txs_timed_out,
+            ),
+            (
+                "txs_reconciled_total",
+                "Outcomes of sent transactions recovered from blocks.",
+                self.txs_reconciled,
+            ),
+            (
+                "blocks_committed_total",
+                "Committed blocks.",
+                self.blocks_committed,
+            ),
+            (
+                "blocks_rejected_total",
+                "Rejected blocks.",
+                self.blocks_rejected,
+            ),
+        ];
+        for (name, help, value) in counters {
+            encoder
+                .family(name, MetricKind::Counter, help)
+                .sample(name, &[], value);
+        }
+        encoder.family(
+            "txs_rejected_by_reason_total",
+            MetricKind::Counter,
+            "Sent transactions that were rejected by the category of the reason.",
+        );
+        for (category, count) in &self.rejection_reasons {
+            encoder.sample(
+                "txs_rejected_by_reason_total",
+                &[("reason", category.as_str())],
+                count,
+            );
+        }
+        let peers = self
+            .peers
+            .iter()
+            .map(|(url, counters)| (url.clone(), counters))
+            .collect::<Vec<_>>();
+        encode_counters(encoder, "peer", &peers);
+        let operations = self
+            .operations
+            .iter()
+            .map(|(operation, counters)| (format!("{:?}", operation), counters))
+            .chain([("Empty".to_owned(), &self.empty_transactions)])
+            .collect::<Vec<_>>();
+        encode_counters(encoder, "operation", &operations);
+        encoder
+            .family(
+                "uptime_seconds",
+                MetricKind::Gauge,
+                "Time since the start of the run.",
+            )
+            .sample("uptime_seconds", &[], self.uptime.secs());
+        encoder
+            .family(
+                "txs_in_flight",
+                MetricKind::Gauge,
+                "Sent transactions waiting for their pipeline event.",
+            )
+            .sample("txs_in_flight", &[], self.in_flight.len());
+        encoder
+            .family(
+                "event_stream_disconnects_total",
+                MetricKind::Counter,
+                "Times the event stream was lost.",
+            )
+            .sample(
+                "event_stream_disconnects_total",
+                &[],
+                self.event_stream_disconnects,
+            );
+        encoder
+            .family(
+                "event_stream_downtime_seconds_total",
+                MetricKind::Counter,
+                "Total time without the event stream.",
+            )
+            .sample(
+                "event_stream_downtime_seconds_total",
+                &[],
+                self.event_stream_downtime_secs,
+            );
+        encoder
+            .family(
+                "commit_latency_seconds",
+                MetricKind::Histogram,
+                "Time from submission to commit of sent transactions.",
+            )
+            .histogram("commit_latency_seconds", &[], &self.commit_latency);
+        encoder
+            .family(
+                "rejection_latency_seconds",
+                MetricKind::Histogram,
+                "Time from submission to rejection of sent transactions.",
+            )
+            .histogram("rejection_latency_seconds", &[], &self.rejection_latency);
+        encoder
+            .family(
+                "send_lateness_seconds",
+                MetricKind::Histogram,
+                "Delay of sends relative to their schedule.",
+            )
+            .histogram("send_lateness_seconds", &[], &self.send_lateness);
+    }
+}
+
+/// Encodes the counters of every part of the sent transactions labelled by `label`,
+/// e.g. `peer_txs_sent_total{peer="..."}`.
+fn encode_counters(encoder: &mut Encoder, label: &str, parts: &[(String, &TxCounters)]) {
+    let counters: [(&str, &str, fn(&TxCounters) -> usize); 5] = [
+        ("txs_sent_total", "Sent transactions", |counters| {
+            counters.txs_sent
+        }),
+        (
+            "txs_failed_total",
+            "Transactions which failed to be submitted",
+            |counters| counters.txs_failed,
+        ),
+        (
+            "txs_committed_total",
+            "Sent transactions that were committed",
+            |counters| counters.txs_committed,
+        ),
+        (
+            "txs_rejected_total",
+            "Sent transactions that were rejected",
+            |counters| counters.txs_rejected,
+        ),
+        (
+            "txs_timed_out_total",
+            "Sent transactions which got no outcome in time",
+            |counters| counters.txs_timed_out,
+        ),
+    ];
+    for (name, help, value) in counters {
+        let name = format!("{}_{}", label, name);
+        encoder.family(
+            &name,
+            MetricKind::Counter,
+            &format!("{} by {}.", help, label),
+        );
+        for (part, counters) in parts {
+            encoder.sample(&name, &[(label, part.as_str())], value(counters));
+        }
+    }
+    let name = format!("{}_commit_latency_seconds", label);
+    encoder.family(
+        &name,
+        MetricKind::Histogram,
+        &format!(
+            "Time from submission to commit of sent transactions by {}.",
+            label
+        ),
+    );
+    for (part, counters) in parts {
+        encoder.histogram(&name, &[(label, part.as_str())], &counters.commit_latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> Hash {
+        Hash::new([byte])
+    }
+
+    #[test]
+    fn commit_before_the_submission_returns_is_ours() {
+        let mut status = Status::default();
+        status.tx_is_sent(hash(1), Instant::now(), "peer", None);
+        status.tx_is_committed(&hash(1));
+        assert_eq!(status.txs_committed, 1);
+        assert_eq!(status.txs_foreign_committed, 0);
+    }
+
+    #[test]
+    fn failed_submission_is_taken_back() {
+        let mut status = Status::default();
+        let operation = Some(Operation::RegisterDomain);
+        status.tx_is_sent(hash(1), Instant::now(), "peer", operation);
+        assert!(status.tx_is_not_sent(&hash(1)));
+        assert!(!status.tx_is_not_sent(&hash(1)));
+        assert_eq!(status.txs_sent, 0);
+        assert_eq!(status.peers["peer"].txs_sent, 0);
+        assert_eq!(status.operations[&Operation::RegisterDomain].txs_sent, 0);
+        assert!(status.in_flight.is_empty());
+    }
+
+    #[test]
+    fn paused_expiry_keeps_transactions_for_reconciliation() {
+        let mut status = Status::default();
+        status.tx_is_sent(hash(1), Instant::now(), "peer", None);
+        status.pause_expiry();
+        status.expire_in_flight(Duration::ZERO);
+        assert_eq!(status.txs_timed_out, 0);
+        status.tx_is_reconciled(&hash(1), None);
+        status.resume_expiry();
+        status.expire_in_flight(Duration::ZERO);
+        assert_eq!(status.txs_committed, 1);
+        assert_eq!(status.txs_reconciled, 1);
+        assert_eq!(status.txs_timed_out, 0);
+    }
+
+    #[test]
+    fn expiry_times_out_transactions() {
+        let mut status = Status::default();
+        status.tx_is_sent(hash(1), Instant::now(), "peer", None);
+        status.expire_in_flight(Duration::ZERO);
+        assert_eq!(status.txs_timed_out, 1);
+        status.tx_is_committed(&hash(1));
+        assert_eq!(status.txs_committed, 0);
+        assert_eq!(status.txs_foreign_committed, 0);
+    }
+
+    #[test]
+    fn in_flight_of_a_resumed_run_is_timed_out() {
+        let mut saved = Status::default();
+        let operation = Some(Operation::RegisterDomain);
+        saved.tx_is_sent(hash(1), Instant::now(), "peer", operation);
+        let in_flight = serde_json::to_string(&saved.in_flight()).expect("Serializable");
+        let mut status = Status::default();
+        status.in_flight_is_lost(serde_json::from_str(&in_flight).expect("Deserializable"));
+        assert_eq!(status.txs_timed_out, 1);
+        assert_eq!(status.peers["peer"].txs_timed_out, 1);
+        assert_eq!(
+            status.operations[&Operation::RegisterDomain].txs_timed_out,
+            1
+        );
+        status.tx_is_committed(&hash(1));
+        assert_eq!(status.txs_committed, 0);
+        assert_eq!(status.txs_foreign_committed, 0);
+    }
 }